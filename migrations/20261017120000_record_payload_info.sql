@@ -0,0 +1,4 @@
+ALTER TABLE masstuffy_records ADD status smallint;
+ALTER TABLE masstuffy_records ADD mime TEXT;
+ALTER TABLE masstuffy_records ADD payload_length bigint;
+ALTER TABLE masstuffy_records ADD payload_digest TEXT;
@@ -0,0 +1,3 @@
+CREATE INDEX masstuffy_record_payload_digests_idx
+    ON masstuffy_records USING btree (collection, payload_digest)
+    WHERE "type" = 'response';
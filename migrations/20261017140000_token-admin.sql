@@ -0,0 +1,2 @@
+-- admin tokens manage other tokens
+ALTER TABLE masstuffy_tokens ADD admin boolean NOT NULL DEFAULT false;
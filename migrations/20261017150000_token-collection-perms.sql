@@ -0,0 +1,7 @@
+-- collection-level permissions, same kinds as the record ones
+ALTER TABLE masstuffy_tokens ADD create_coll_perms_kind smallint NOT NULL DEFAULT 0;
+ALTER TABLE masstuffy_tokens ADD create_coll_perms      TEXT     NOT NULL DEFAULT '';
+ALTER TABLE masstuffy_tokens ADD delete_coll_perms_kind smallint NOT NULL DEFAULT 0;
+ALTER TABLE masstuffy_tokens ADD delete_coll_perms      TEXT     NOT NULL DEFAULT '';
+ALTER TABLE masstuffy_tokens ADD list_coll_perms_kind   smallint NOT NULL DEFAULT 0;
+ALTER TABLE masstuffy_tokens ADD list_coll_perms        TEXT     NOT NULL DEFAULT '';
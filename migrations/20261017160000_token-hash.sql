@@ -0,0 +1,14 @@
+-- tokens are stored as a keyed hash (see `hash_token`),
+-- plain ones are hashed when the server starts or `init_db` runs.
+ALTER TABLE masstuffy_tokens ALTER COLUMN token TYPE TEXT;
+ALTER TABLE masstuffy_tokens ADD hashed boolean NOT NULL DEFAULT false;
+
+-- public identifier, tokens can't be told from their hash
+ALTER TABLE masstuffy_tokens ADD id TEXT;
+UPDATE masstuffy_tokens SET id = gen_random_uuid()::text;
+ALTER TABLE masstuffy_tokens ALTER COLUMN id SET NOT NULL;
+CREATE UNIQUE INDEX masstuffy_token_ids_idx
+    ON masstuffy_tokens USING btree (id);
+
+ALTER TABLE masstuffy_tokens ADD expires_at   timestamptz;
+ALTER TABLE masstuffy_tokens ADD last_used_at timestamptz;
@@ -0,0 +1,12 @@
+-- requests and bytes served per client and day (see the rate limiter of the server)
+CREATE TABLE masstuffy_usage (
+    -- token id, or `ip:[address]` for anonymous clients
+    client      TEXT        NOT NULL,
+    day         date        NOT NULL,
+    requests    bigint      NOT NULL DEFAULT 0,
+    bytes       bigint      NOT NULL DEFAULT 0,
+    -- requests answered with 429
+    throttled   bigint      NOT NULL DEFAULT 0,
+
+    PRIMARY KEY (client, day)
+);
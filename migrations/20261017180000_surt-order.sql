@@ -0,0 +1,7 @@
+-- SURT key of a massaged url (see `warc::cdxj::surt`), CDX indexes are sorted by it
+CREATE FUNCTION masstuffy_surt(massaged_url TEXT) RETURNS TEXT
+    LANGUAGE sql IMMUTABLE STRICT
+    AS $$ SELECT regexp_replace(lower(massaged_url), '^([^):]*),www(?=[:)])', '\1') $$;
+
+CREATE INDEX masstuffy_record_surt_idx
+    ON masstuffy_records USING btree ((masstuffy_surt(massaged_url) COLLATE "C"), date, id);
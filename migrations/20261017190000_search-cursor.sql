@@ -0,0 +1,7 @@
+-- searches resume after the sort key of the last record, row ids change when a collection is rewritten
+DROP INDEX masstuffy_record_surt_idx;
+CREATE INDEX masstuffy_record_surt_idx
+    ON masstuffy_records USING btree ((masstuffy_surt(massaged_url) COLLATE "C"), date, identifier, collection, filename, "offset");
+
+CREATE INDEX masstuffy_record_date_idx
+    ON masstuffy_records USING btree (date, identifier, collection, filename, "offset");
@@ -30,6 +30,14 @@ struct Args {
     /// dictionary's id to use
     #[arg(short, long)]
     dict_id: Option<u32>,
+
+    /// compression algorithm (zstd, requires a dictionary, or gzip)
+    #[arg(short, long)]
+    compression: Option<String>,
+
+    /// store http responses whose payload is already in the collection as `revisit` records
+    #[arg(long)]
+    dedup: bool,
 }
 
 pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
@@ -43,14 +51,8 @@ pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
         return Ok(1);
     }
 
-    fs.create_collection(
-        args.collection,
-        if let Some(dict_id) = args.dict_id {
-            Some(("zstd".to_string(), dict_id))
-        } else {
-            None
-        }
-    ).await?;
+    let compression = args.compression.or(args.dict_id.map(|_| "zstd".to_string()));
+    fs.create_collection(args.collection, compression, args.dict_id, args.dedup).await?;
 
     Ok(0)
 }
\ No newline at end of file
@@ -18,7 +18,8 @@
 
 use std::{error::Error};
 use clap::Parser;
-use masstuffy::{database, filesystem, permissions::{TokenInfo, TokenPermission}};
+use chrono::{DateTime, Utc};
+use masstuffy::{database, filesystem, permissions::{generate_token, hash_token, TokenInfo, TokenPermission}};
 
 #[derive(Parser)]
 struct Args {
@@ -36,7 +37,6 @@ struct Args {
     #[arg(long)]
     read_list: Option<Vec<String>>,
 
-    
     /// create token with _any_ write permission.
     #[arg(long, default_value_t = false)]
     write_any: bool,
@@ -47,7 +47,6 @@ struct Args {
     #[arg(long)]
     write_list: Option<Vec<String>>,
 
-
     /// create token with _any_ delete permission.
     #[arg(long, default_value_t = false)]
     delete_any: bool,
@@ -56,7 +55,45 @@ struct Args {
     delete_prefix: Option<String>,
     /// create token with _list_ delete permission.
     #[arg(long)]
-    delete_list: Option<Vec<String>>
+    delete_list: Option<Vec<String>>,
+
+    /// create token with _any_ create collection permission.
+    #[arg(long, default_value_t = false)]
+    create_coll_any: bool,
+    /// create token with _prefix_ create collection permission.
+    #[arg(long)]
+    create_coll_prefix: Option<String>,
+    /// create token with _list_ create collection permission.
+    #[arg(long)]
+    create_coll_list: Option<Vec<String>>,
+
+    /// create token with _any_ delete collection permission.
+    #[arg(long, default_value_t = false)]
+    delete_coll_any: bool,
+    /// create token with _prefix_ delete collection permission.
+    #[arg(long)]
+    delete_coll_prefix: Option<String>,
+    /// create token with _list_ delete collection permission.
+    #[arg(long)]
+    delete_coll_list: Option<Vec<String>>,
+
+    /// create token with _any_ list collections permission.
+    #[arg(long, default_value_t = false)]
+    list_coll_any: bool,
+    /// create token with _prefix_ list collections permission.
+    #[arg(long)]
+    list_coll_prefix: Option<String>,
+    /// create token with _list_ list collections permission.
+    #[arg(long)]
+    list_coll_list: Option<Vec<String>>,
+
+    /// allow the token to manage tokens (`/tokens` endpoints).
+    #[arg(long, default_value_t = false)]
+    admin: bool,
+
+    /// expiry date of the token (RFC 3339, e.g. `2026-12-31T23:59:59Z`).
+    #[arg(long)]
+    expires_at: Option<String>
 }
 
 pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
@@ -65,6 +102,9 @@ pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
     let mut read_perms: TokenPermission = TokenPermission::None;
     let mut write_perms: TokenPermission = TokenPermission::None;
     let mut delete_perms: TokenPermission = TokenPermission::None;
+    let mut create_coll_perms: TokenPermission = TokenPermission::None;
+    let mut delete_coll_perms: TokenPermission = TokenPermission::None;
+    let mut list_coll_perms: TokenPermission = TokenPermission::None;
 
     if args.read_any {
         read_perms = TokenPermission::Any;
@@ -90,15 +130,51 @@ pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
         delete_perms = TokenPermission::Prefix(p)
     }
 
-    let token = TokenInfo{
-        token: uuid::Uuid::new_v4().to_string(),
-        comment: args.comment,
-        read_perms, write_perms, delete_perms};
+    if args.create_coll_any {
+        create_coll_perms = TokenPermission::Any;
+    } else if let Some(l) = args.create_coll_list {
+        create_coll_perms = TokenPermission::List(l);
+    } else if let Some(p) = args.create_coll_prefix {
+        create_coll_perms = TokenPermission::Prefix(p)
+    }
+
+    if args.delete_coll_any {
+        delete_coll_perms = TokenPermission::Any;
+    } else if let Some(l) = args.delete_coll_list {
+        delete_coll_perms = TokenPermission::List(l);
+    } else if let Some(p) = args.delete_coll_prefix {
+        delete_coll_perms = TokenPermission::Prefix(p)
+    }
+
+    if args.list_coll_any {
+        list_coll_perms = TokenPermission::Any;
+    } else if let Some(l) = args.list_coll_list {
+        list_coll_perms = TokenPermission::List(l);
+    } else if let Some(p) = args.list_coll_prefix {
+        list_coll_perms = TokenPermission::Prefix(p)
+    }
+
+    let expires_at = match args.expires_at {
+        Some(date) => Some(DateTime::parse_from_rfc3339(&date)?.with_timezone(&Utc)),
+        None => None
+    };
 
     let fs = filesystem::init().await?;
     let db = database::DBManager::new(&fs.get_database_conn_string());
 
-    println!("{}", token.token);
+    let plain_token = generate_token();
+    let token = TokenInfo{
+        id: uuid::Uuid::new_v4().to_string(),
+        token_hash: hash_token(fs.get_secret_key()?, &plain_token),
+        comment: args.comment,
+        read_perms, write_perms, delete_perms,
+        admin: args.admin,
+        create_coll_perms, delete_coll_perms, list_coll_perms,
+        expires_at, last_used_at: None};
+
+    // the token can't be recovered from the database
+    eprintln!("token id: {}", token.id);
+    println!("{}", plain_token);
     db.create_permissions(token).await?;
 
     Ok(0)
@@ -0,0 +1,51 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use std::error::Error;
+use clap::Parser;
+
+use log::error;
+use masstuffy::{database::DBManager, filesystem::init};
+
+#[derive(Parser)]
+struct Args {
+    /// record's identifier (WARC-Record-ID)
+    id: String
+}
+
+pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let args = Args::parse_from(&argv[1..]);
+
+    let fs = init().await
+        .expect("unable to initialise fs");
+    let db = DBManager::new(&fs.get_database_conn_string());
+
+    let record = db.get_record_from_id(args.id.clone(), None).await;
+    if let Err(x) = record {
+        error!("unable to find record `{}`: {}", args.id, x);
+        return Ok(1);
+    }
+    let record = record.unwrap();
+
+    if !fs.delete_record(&record.collection, &record.identifier, &db).await? {
+        error!("collection `{}` not found", record.collection);
+        return Ok(1);
+    }
+
+    Ok(0)
+}
@@ -19,10 +19,12 @@
 use std::error::Error;
 use clap::Parser;
 
-use masstuffy::{database::DBManager, filesystem::init};
+use log::error;
+use masstuffy::{database::DBManager, filesystem::init, permissions::hash_token};
 
 #[derive(Parser)]
 struct Args {
+    /// token's id (see `list_tokens`) or the token itself
     token: String
 }
 
@@ -33,7 +35,16 @@ pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
         .expect("unable to initialise fs");
     let db = DBManager::new(&fs.get_database_conn_string());
 
-    db.delete_permissions(&args.token).await?;
+    let mut deleted = db.delete_permissions(&args.token).await?;
+    if deleted == 0
+        && let Some(ti) = db.get_permissions(&hash_token(fs.get_secret_key()?, &args.token)).await? {
+        deleted = db.delete_permissions(&ti.id).await?;
+    }
+
+    if deleted == 0 {
+        error!("no such token");
+        return Ok(1);
+    }
 
     Ok(0)
 }
\ No newline at end of file
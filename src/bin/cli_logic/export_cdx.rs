@@ -0,0 +1,159 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use std::{cmp::Reverse, collections::BinaryHeap, error::Error, fs::File, io::{stdout, BufRead, BufReader, BufWriter, Write}};
+
+use clap::{Parser, ValueEnum};
+use log::{error, warn};
+use masstuffy::{filesystem::{self, CollID}, warc::cdxj::{sort_entries, CDXJEntry, CDX11_HEADER}};
+use uuid::Uuid;
+
+/// entries sorted in memory at once, bigger exports are merged from sorted runs.
+const SORT_RUN_SIZE: usize = 500_000;
+
+#[derive(Clone, ValueEnum)]
+enum Format {
+    Cdxj,
+    Cdx11
+}
+
+#[derive(Parser)]
+struct Args {
+    /// collections to export (all of them if none)
+    collections: Vec<String>,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Cdxj)]
+    format: Format
+}
+
+pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let args = Args::parse_from(&argv[1..]);
+
+    let fs = filesystem::init().await?;
+    let slugs = if args.collections.is_empty() {
+        fs.get_collection_list().await
+    } else {
+        args.collections
+    };
+
+    let (tmp_dir, _) = fs.get_buffer_path(&format!("export_cdx.{}", Uuid::new_v4()), true).await?;
+    let mut runs: Vec<String> = Vec::new();
+    let mut entries: Vec<CDXJEntry> = Vec::new();
+    let ret: Result<i32, Box<dyn Error>> = async {
+        for coll_slug in slugs {
+            let Some(coll) = fs.get_collection(CollID::Slug(coll_slug.clone())).await else {
+                error!("no such collection: {}", coll_slug);
+                return Ok(1);
+            };
+            let coll = coll.read().await;
+            let coll_uuid = coll.get_uuid().await;
+
+            let tombstones = coll.get_tombstones().await?;
+            let mut reader = coll.iter_cdx().await?;
+            while let Some(record_cdx) = reader.async_next().await? {
+                if record_cdx.is_tombstone() || tombstones.contains(&record_cdx.get_record_id()) {
+                    continue;
+                }
+
+                let (Some(url), Some(filename), Some(offset), Some(length)) = (
+                    record_cdx.get_url(), record_cdx.get_file_name(),
+                    record_cdx.get_file_offset(), record_cdx.get_raw_size()) else {
+                    continue;
+                };
+
+                match CDXJEntry::new(
+                    &url, &record_cdx.get_date(),
+                    format!("{}/{}", coll_uuid, filename), offset as u64, length) {
+                    Ok(entry) => entries.push(entry.with_details(
+                        &record_cdx.get_record_type(), record_cdx.get_mime(),
+                        record_cdx.get_status(), record_cdx.get_payload_digest())),
+                    Err(x) => warn!("{}: unable to index {}: {}", coll_slug, url, x)
+                }
+
+                if entries.len() >= SORT_RUN_SIZE {
+                    let path = format!("{}run.{}", tmp_dir, runs.len());
+                    write_sorted(&mut entries, &args.format, File::create(&path)?)?;
+                    runs.push(path);
+                }
+            }
+        }
+
+        let mut writer = BufWriter::new(stdout());
+        if let Format::Cdx11 = args.format {
+            writeln!(writer, "{}", CDX11_HEADER)?;
+        }
+        if runs.is_empty() {
+            write_sorted(&mut entries, &args.format, &mut writer)?;
+        } else {
+            if !entries.is_empty() {
+                let path = format!("{}run.{}", tmp_dir, runs.len());
+                write_sorted(&mut entries, &args.format, File::create(&path)?)?;
+                runs.push(path);
+            }
+            merge_runs(&runs, &mut writer)?;
+        }
+        writer.flush()?;
+
+        Ok(0)
+    }.await;
+
+    tokio::fs::remove_dir_all(&tmp_dir).await?;
+    ret
+}
+
+/// sorts and writes then clears `entries`.
+fn write_sorted<W: Write>(entries: &mut Vec<CDXJEntry>, format: &Format, writer: W) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    sort_entries(entries);
+    for entry in entries.iter() {
+        match format {
+            Format::Cdxj => writeln!(writer, "{}", entry.to_cdxj())?,
+            Format::Cdx11 => writeln!(writer, "{}", entry.to_cdx11())?
+        }
+    }
+    entries.clear();
+    writer.flush()
+}
+
+/// both formats start with `[surt] [timestamp] `, lines are compared on it.
+fn sort_key(line: &str) -> (String, String) {
+    let mut fields = line.splitn(3, ' ');
+    (fields.next().unwrap_or_default().to_string(), fields.next().unwrap_or_default().to_string())
+}
+
+/// merges sorted runs, keeping a single line of each in memory.
+fn merge_runs<W: Write>(runs: &[String], writer: &mut W) -> std::io::Result<()> {
+    let mut readers = Vec::new();
+    let mut heap = BinaryHeap::new();
+    for (i, path) in runs.iter().enumerate() {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        if let Some(line) = lines.next().transpose()? {
+            heap.push(Reverse((sort_key(&line), i, line)));
+        }
+        readers.push(lines);
+    }
+
+    while let Some(Reverse((_, i, line))) = heap.pop() {
+        writeln!(writer, "{}", line)?;
+        if let Some(line) = readers[i].next().transpose()? {
+            heap.push(Reverse((sort_key(&line), i, line)));
+        }
+    }
+
+    Ok(())
+}
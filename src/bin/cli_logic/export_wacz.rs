@@ -0,0 +1,110 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use std::{error::Error, fs::File, io::BufWriter};
+
+use clap::Parser;
+use log::{error, info, warn};
+use masstuffy::{database::{DBManager, SearchFilters}, filesystem::{self, CollID}, warc::{massaged_url::Match, wacz::WaczWriter}};
+
+#[derive(Parser)]
+struct Args {
+    /// wacz file to write
+    output: String,
+
+    /// export a whole collection (slug) instead of search results
+    #[arg(short, long)]
+    collection: Option<String>,
+
+    /// match _domain_ and its subdomains
+    #[arg(long)]
+    domain: Option<String>,
+    /// match _host_ (same as domain but exact match)
+    #[arg(long)]
+    host: Option<String>,
+    /// match uris with _port_
+    #[arg(long)]
+    port: Option<u16>,
+    /// match any path starting with _path_
+    #[arg(long)]
+    path: Option<String>,
+    /// match path exactly equal to _exact path_
+    #[arg(long)]
+    exact_path: Option<String>,
+
+    /// maximum number of search results
+    #[arg(long, default_value_t = 100)]
+    limit: i64,
+
+    /// package title
+    #[arg(long)]
+    title: Option<String>
+}
+
+pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let args = Args::parse_from(&argv[1..]);
+
+    let fs = filesystem::init().await?;
+
+    let title = args.title.clone().unwrap_or(match &args.collection {
+        Some(slug) => slug.clone(),
+        None => "search results".to_string()
+    });
+    let mut wacz = WaczWriter::new(BufWriter::new(File::create(&args.output)?), &title)?;
+    let mut count = 0;
+
+    if let Some(coll_slug) = &args.collection {
+        let Some(coll) = fs.get_collection(CollID::Slug(coll_slug.clone())).await else {
+            error!("no such collection: {}", coll_slug);
+            return Ok(1);
+        };
+        count = coll.read().await.export_wacz(&mut wacz).await?;
+    } else {
+        let db = DBManager::new(&fs.get_database_conn_string());
+
+        let mut host = Match::None;
+        let mut path = Match::None;
+
+        if let Some(p) = args.exact_path {
+            path = Match::ExactMatch(p)
+        } else if let Some(p) = args.path {
+            path = Match::PartialMatch(p)
+        }
+
+        if let Some(h) = args.host {
+            host = Match::ExactMatch(h)
+        } else if let Some(h) = args.domain {
+            host = Match::PartialMatch(h)
+        }
+
+        for r in db.search(SearchFilters{host, port: args.port, path, ..Default::default()}, None, args.limit).await? {
+            match fs.get_record(&r.collection, &r.filename, r.offset).await? {
+                Some(record) => {
+                    wacz.add_record(&record).await?;
+                    count += 1;
+                },
+                None => warn!("record {} not found", r.identifier)
+            }
+        }
+    }
+
+    wacz.finish()?;
+    info!("{} record(s) written to {}", count, args.output);
+
+    Ok(0)
+}
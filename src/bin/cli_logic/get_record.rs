@@ -44,7 +44,7 @@ pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
     let db = DBManager::new(&fs.get_database_conn_string());
 
     let record_cdx = if args.by_id {
-        db.get_record_from_id(args.query).await?
+        db.get_record_from_id(args.query, None).await?
     } else {
         let date_str = if let Some(d) = args.date {
             d
@@ -52,7 +52,7 @@ pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
             Utc::now().naive_utc().format("%Y%m%d%H%M%S").to_string()
         };
 
-        db.get_record_from_uri(&date_str, &args.query).await?
+        db.get_record_from_uri(&date_str, &args.query, None).await?
     };
 
     info!("{}", record_cdx.collection);
@@ -38,13 +38,12 @@ pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
         let coll = fs.get_collection(CollID::Slug(coll_slug.clone())).await.unwrap();
         let coll = coll.read().await;
 
+        let tombstones = coll.get_tombstones().await?;
         let mut reader = coll.iter_cdx().await?;
-        loop {
-            let record_cdx = reader.async_next().await;
-            if let None = record_cdx {
-                break
+        while let Some(record_cdx) = reader.async_next().await? {
+            if record_cdx.is_tombstone() || tombstones.contains(&record_cdx.get_record_id()) {
+                continue;
             }
-            let record_cdx = record_cdx.unwrap();
             let record = coll.get_record(
                 &record_cdx.get_file_name().unwrap(), 
                 record_cdx.get_file_offset().unwrap()).await?.unwrap();
@@ -29,6 +29,11 @@ pub async fn main(_argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
 
     db.setup_db().await;
 
+    let hashed = db.hash_plain_tokens(fs.get_secret_key()?).await?;
+    if hashed != 0 {
+        info!("{} plain token(s) hashed", hashed);
+    }
+
     let collections = fs.get_collection_list().await;
     
     for col in &collections {
@@ -36,6 +41,7 @@ pub async fn main(_argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
         // TODO: optimise
         if let Some(col) = fs.get_collection(CollID::Slug(col.clone())).await {
             let uuid = col.read().await.get_uuid().await;
+            let tombstones = col.read().await.get_tombstones().await?;
             let mut reader = col.read().await.iter_cdx().await?;
 
             let (dict_id, dict_algo) = col.read().await.get_dict().await;
@@ -45,10 +51,20 @@ pub async fn main(_argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
                 None
             };
 
-            while let Some(record) = reader.async_next().await {
+            while let Some(record) = reader.async_next().await? {
+                if record.is_tombstone() {
+                    continue;
+                }
+
+                let flags = if tombstones.contains(&record.get_record_id()) {
+                    0
+                } else {
+                    RECORD_FLAG_ACTIVE
+                };
+
                 if let Err(x) = db.insert_record(
                     &uuid, &record,
-                    RECORD_FLAG_ACTIVE,
+                    flags,
                     dict_id, dict_algo.as_deref()).await {
                     error!("error when inserting record: {}", x);
                 }
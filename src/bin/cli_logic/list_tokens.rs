@@ -24,11 +24,17 @@ pub async fn main(_: Vec<String>) -> Result<i32, Box<dyn Error>> {
     let db = database::DBManager::new(&fs.get_database_conn_string());
 
     for token in db.get_all_permissions().await? {
-        println!("{}:", token.token);
+        println!("{}:", token.id);
         println!("\tcomment      : {}", token.comment);
         println!("\tread access  : {}", token.read_perms);
         println!("\twrite access : {}", token.write_perms);
         println!("\tdelete access: {}", token.delete_perms);
+        println!("\tcreate colls : {}", token.create_coll_perms);
+        println!("\tdelete colls : {}", token.delete_coll_perms);
+        println!("\tlist colls   : {}", token.list_coll_perms);
+        println!("\tadmin        : {}", token.admin);
+        println!("\texpires at   : {}", token.expires_at.map_or("never".to_string(), |d| d.to_rfc3339()));
+        println!("\tlast used at : {}", token.last_used_at.map_or("never".to_string(), |d| d.to_rfc3339()));
     }
 
     Ok(0)
@@ -16,7 +16,7 @@
  *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
 **/
 
-use std::{env::args, error::Error};
+use std::{env::args, error::Error, fmt::Write};
 use log::error;
 use tokio;
 
@@ -32,7 +32,16 @@ mod delete_collection;
 mod create_token;
 mod list_tokens;
 mod delete_token;
+mod rotate_token;
+mod sign_url;
+mod usage;
 mod grep;
+mod delete_record;
+mod compact;
+mod reindex;
+mod export_cdx;
+mod export_wacz;
+mod verify;
 
 fn print_help(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
     print!(
@@ -51,7 +60,16 @@ delete_collection - delete a collection
 create_token      - create an access token
 list_tokens       - list access tokens
 delete_token      - delete an access token
+rotate_token      - replace an access token, keeping its permissions
+sign_url          - sign a record url so it can be fetched without a token
+usage             - show requests and bytes served per client
 grep              - search text inside objects
+delete_record     - delete a record from its id
+compact           - reclaim space used by deleted records
+reindex           - regenerate the index from record files
+export_cdx        - export a CDXJ or CDX11 index of collections
+export_wacz       - export a collection or search results as a WACZ package
+verify            - check records, indexes and database
 "#,
     argv[0]);
     Ok(0)
@@ -80,12 +98,28 @@ pub async fn main() {
         "create_token" => create_token::main(argv).await,
         "list_tokens" => list_tokens::main(argv).await,
         "delete_token" => delete_token::main(argv).await,
+        "rotate_token" => rotate_token::main(argv).await,
+        "sign_url" => sign_url::main(argv).await,
+        "usage" => usage::main(argv).await,
         "grep" => grep::main(argv).await,
+        "delete_record" => delete_record::main(argv).await,
+        "compact" => compact::main(argv).await,
+        "reindex" => reindex::main(argv).await,
+        "export_cdx" => export_cdx::main(argv).await,
+        "export_wacz" => export_wacz::main(argv).await,
+        "verify" => verify::main(argv).await,
         _ => print_help(argv),
     };
 
     if let Err(x) = ret {
-        error!("{}", x);
+        let mut msg = x.to_string();
+        let mut source = x.source();
+        while let Some(cause) = source {
+            write!(msg, ": {}", cause).unwrap();
+            source = cause.source();
+        }
+        error!("{}", msg);
+        std::process::exit(1);
     } else {
         std::process::exit(ret.ok().unwrap());
     }
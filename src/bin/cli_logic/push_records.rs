@@ -17,18 +17,30 @@
 **/
 
 use std::error::Error;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
 use log::error;
-use masstuffy::{database::{structs::RECORD_FLAG_ACTIVE, DBManager}, filesystem::{init, CollID}, warc::WarcReader};
+use masstuffy::{database::DBManager, filesystem::{init, CollID}, warc::arc::ArcReader};
+use uuid::Uuid;
+
+#[derive(Clone, PartialEq, ValueEnum)]
+enum Format {
+    Warc,
+    Arc,
+    Wacz
+}
 
 #[derive(Parser)]
 struct Args {
-    /// warc file holding records
+    /// warc, arc or wacz file holding records
     source: String,
 
     /// collection's slug to put records into
-    destination: String
+    destination: String,
+
+    /// source format (guessed from the extension by default)
+    #[arg(short, long, value_enum)]
+    format: Option<Format>
 }
 
 pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
@@ -59,26 +71,33 @@ pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
         return Ok(1);
     }
     let coll = coll.unwrap();
-    let coll_uuid = coll.read().await.get_uuid().await;
 
-    let (dict_id, dict_algo) = coll.read().await.get_dict().await;
-    let dict_id = if let Some(dict_id) = dict_id {
-        Some(dict_id as i64)
-    } else {
-        None
-    };
+    let format = args.format.unwrap_or(
+        if args.source.ends_with(".arc") || args.source.ends_with(".arc.gz") {
+            Format::Arc
+        } else if args.source.ends_with(".wacz") {
+            Format::Wacz
+        } else {
+            Format::Warc
+        });
 
-    let mut reader = WarcReader::from_file(&args.source).await?;
-    while let Some(record) = reader.async_next().await {
-        let cdx = coll.write().await.add_warc(&record).await?;
-        if let Some(db) = &dbm {
-            db.insert_record(
-                &coll_uuid, &cdx,
-                RECORD_FLAG_ACTIVE,
-                dict_id, dict_algo.as_deref()
-            ).await?; // TODO: bulk insert
+    match format {
+        Format::Warc => {
+            coll.read().await.import_warc_file(&args.source, dbm.as_ref()).await?;
+        },
+        Format::Wacz => {
+            let (tmp_dir, _) = fs.get_buffer_path(&format!("wacz.{}", Uuid::new_v4()), true).await?;
+            let ret = coll.read().await.import_wacz(&args.source, &tmp_dir, dbm.as_ref()).await;
+            tokio::fs::remove_dir_all(&tmp_dir).await?;
+            ret?;
+        },
+        Format::Arc => {
+            let mut reader = ArcReader::from_file(&args.source).await?;
+            while let Some(record) = reader.async_next().await? {
+                coll.read().await.ingest_warc(&record, dbm.as_ref()).await?;
+            }
         }
     }
 
     Ok(0)
-}
\ No newline at end of file
+}
@@ -34,6 +34,7 @@ pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
 
     let fs = filesystem::init().await?;
     let db = DBManager::new(&fs.get_database_conn_string());
+    fs.resync_databases(&db).await?;
 
     let coll = fs.get_collection(CollID::Slug(args.collection.clone())).await.unwrap();
     let coll = coll.read().await;
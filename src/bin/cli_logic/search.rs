@@ -19,7 +19,8 @@
 use std::error::Error;
 
 use clap::Parser;
-use masstuffy::{constants::MASSTUFFY_DATE_FMT, database::DBManager, filesystem, warc::massaged_url::Match};
+use log::error;
+use masstuffy::{constants::MASSTUFFY_DATE_FMT, database::{DBManager, SearchCursor, SearchFilters}, filesystem::{self, CollID}, utils::dates::parse_date_prefix, warc::massaged_url::Match};
 
 #[derive(Parser)]
 struct Args {
@@ -37,7 +38,31 @@ struct Args {
     path: Option<String>,
     /// match path exactly equal to _exact path_
     #[arg(long)]
-    exact_path: Option<String>
+    exact_path: Option<String>,
+    /// match records captured from _from_ (YYYY[mm[dd[HH[MM[SS]]]]])
+    #[arg(long)]
+    from: Option<String>,
+    /// match records captured until _to_ (YYYY[mm[dd[HH[MM[SS]]]]])
+    #[arg(long)]
+    to: Option<String>,
+    /// match records of type _type_ (response, revisit, ...)
+    #[arg(long = "type")]
+    record_type: Option<String>,
+    /// match records of the collection _collection_ (slug)
+    #[arg(short, long)]
+    collection: Option<String>,
+    /// match http responses with MIME type _mime_
+    #[arg(long)]
+    mime: Option<String>,
+    /// match http responses with status code _status_
+    #[arg(long)]
+    status: Option<i16>,
+    /// resume the search after the record _after_ (printed after a full page)
+    #[arg(long)]
+    after: Option<SearchCursor>,
+    /// maximum number of records
+    #[arg(long, default_value_t = 100)]
+    limit: i64
 }
 
 pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
@@ -61,7 +86,28 @@ pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
         host = Match::PartialMatch(h)
     }
 
-    let results = db.search(host, args.port, path, 100).await?;
+    let mut filters = SearchFilters{
+        host, port: args.port, path,
+        record_type: args.record_type,
+        mime: args.mime.map(|m| m.to_lowercase()),
+        status: args.status,
+        ..Default::default()
+    };
+    if let Some(from) = &args.from {
+        filters.from = Some(parse_date_prefix(from, false)?);
+    }
+    if let Some(to) = &args.to {
+        filters.to = Some(parse_date_prefix(to, true)?);
+    }
+    if let Some(slug) = args.collection {
+        let Some(coll) = fs.get_collection(CollID::Slug(slug.clone())).await else {
+            error!("no such collection: {}", slug);
+            return Ok(1);
+        };
+        filters.collection = Some(coll.read().await.get_uuid().await);
+    }
+
+    let results = db.search(filters, args.after.as_ref(), args.limit).await?;
 
     for r in &results {
         println!(
@@ -74,5 +120,10 @@ pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
         );
     }
 
+    /* keep stdout for the results */
+    if results.len() as i64 == args.limit && let Some(last) = results.last() {
+        eprintln!("more results with --after {}", SearchCursor::after(last));
+    }
+
     Ok(0)
 }
\ No newline at end of file
@@ -0,0 +1,84 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use std::error::Error;
+use chrono::Utc;
+use clap::Parser;
+
+use log::error;
+use masstuffy::{database::DBManager, filesystem::{init, CollID}, signed_urls::{parse_record_path, sign_path}};
+
+#[derive(Parser)]
+struct Args {
+    /// `/id/:flags/:id` or `/url/:flags/:date/*url`
+    path: String,
+
+    /// lifetime of the url in seconds (`signed_url_max_lifetime` by default)
+    #[arg(short, long)]
+    expires_in: Option<i64>,
+
+    /// flags the url may be requested with (those of the path by default)
+    #[arg(short, long)]
+    flags: Option<String>,
+
+    /// slug of the record's collection (identifiers are not unique across collections)
+    #[arg(short, long)]
+    collection: Option<String>
+}
+
+pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let args = Args::parse_from(&argv[1..]);
+
+    let fs = init().await
+        .expect("unable to initialise fs");
+
+    let db = DBManager::new(&fs.get_database_conn_string());
+
+    let Some((_, resource)) = parse_record_path(&args.path) else {
+        error!("not a record path: {}", args.path);
+        return Ok(1);
+    };
+
+    let collections = match &args.collection {
+        Some(slug) => match fs.get_collection(CollID::Slug(slug.clone())).await {
+            Some(coll) => Some(vec![coll.read().await.get_uuid().await]),
+            None => {
+                error!("collection `{}` doesn't exist", slug);
+                return Ok(1);
+            }
+        },
+        None => None
+    };
+
+    /* the signature is bound to the record's collection */
+    let record = if let Some(id) = resource.strip_prefix("/id/") {
+        db.get_record_from_id(id.to_string(), collections.as_deref()).await
+    } else {
+        let (date, url) = resource["/url/".len()..].split_once('/').unwrap();
+        db.get_record_from_uri(&date.to_string(), &url.to_string(), collections.as_deref()).await
+    };
+    let Ok(record) = record else {
+        error!("record not found");
+        return Ok(1);
+    };
+
+    let expires = Utc::now().timestamp() + args.expires_in.unwrap_or(fs.get_signed_url_max_lifetime());
+    println!("{}", sign_path(fs.get_secret_key()?, &args.path, &record.collection, expires, args.flags.as_deref())?);
+
+    Ok(0)
+}
@@ -0,0 +1,54 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use std::error::Error;
+use clap::Parser;
+
+use masstuffy::{database::DBManager, filesystem::init, utils::dates::parse_date_prefix};
+
+#[derive(Parser)]
+struct Args {
+    /// token's id (see `list_tokens`) or `ip:[address]` for anonymous clients
+    #[arg(short, long)]
+    client: Option<String>,
+
+    /// days from this date (`YYYY[mm[dd]]`)
+    #[arg(short, long)]
+    since: Option<String>
+}
+
+pub async fn main(argv: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let args = Args::parse_from(&argv[1..]);
+
+    let fs = init().await
+        .expect("unable to initialise fs");
+    let db = DBManager::new(&fs.get_database_conn_string());
+
+    let since = match &args.since {
+        Some(since) => Some(parse_date_prefix(since, false)?.date()),
+        None => None
+    };
+
+    println!("day\tclient\trequests\tbytes\tthrottled");
+    for usage in db.get_usage(args.client.as_deref(), since).await? {
+        println!("{}\t{}\t{}\t{}\t{}",
+            usage.day, usage.client, usage.requests, usage.bytes, usage.throttled);
+    }
+
+    Ok(0)
+}
@@ -20,20 +20,26 @@ use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 use masstuffy::{database::structs::RECORD_FLAG_ACTIVE, filesystem::CollID, permissions::{PermissionType}, warc::{cdx::{self, CDXRecord}, read_record}};
 use serde::Deserialize;
+use uuid::Uuid;
 use serde_json::json;
 use tide::{http::bail, Request, Response};
 use masstuffy::filesystem::collections::CollectionInfo;
-use crate::server_logic::{assert_access_http, AppState};
+use crate::server_logic::{assert_access_http, assert_admin_http, token_info, wacz_buffer, wacz_response, AppState};
 
 const WARC_RECORD_BUFFER_SIZE: usize = 50_000_000;
 
+/// only lists the collections the token has the list permission on.
 pub async fn list_collections(req: Request<AppState>) -> tide::Result {
+    let token_info = token_info(&req).await?;
     let fs = req.state().fs.read().await;
 
     let mut collection_infos: Vec<CollectionInfo> = Vec::new();
     let collections = fs.get_collection_list().await;
 
     for col in collections {
+        if !token_info.list_coll_perms.check(&col) {
+            continue;
+        }
         if let Some(col) = fs.get_collection(CollID::Slug(col)).await {
             collection_infos.push(col.read().await.get_info().await);
         }
@@ -48,26 +54,48 @@ pub async fn list_collections(req: Request<AppState>) -> tide::Result {
 #[derive(Deserialize)]
 struct CreateCollectionParams {
     slug: String,
-    dict_id: u32,
+    dict_id: Option<u32>,
     comp_algo: Option<String>,
+    #[serde(default)]
+    dedup: bool,
 }
 
 pub async fn create_collection(mut req: Request<AppState>) -> tide::Result {
     let data: CreateCollectionParams = req.body_json().await?;
 
-    let dictionary = if let Some(algo) = data.comp_algo {
-        Some((algo, data.dict_id))
+    assert_access_http(&req, PermissionType::CREATE, &data.slug).await?;
+
+    let dict_id = if data.comp_algo.is_some() {
+        data.dict_id
     } else {
         None
     };
 
     let result = req.state().fs.write().await.
-    create_collection(data.slug, dictionary).await?;
+    create_collection(data.slug, data.comp_algo, dict_id, data.dedup).await?;
 
     Ok(Response::builder(200)
         .body(json!(result)).build())
 }
 
+pub async fn delete_collection(req: Request<AppState>) -> tide::Result {
+    let coll = req.state().fs.read().await
+        .get_collection(CollID::Uuid(req.param("collection_uuid").unwrap().to_string())).await;
+
+    let Some(coll) = coll else {
+        return Ok(Response::builder(404).body("collection not found").build());
+    };
+    let slug = coll.read().await.get_slug().await;
+    drop(coll);
+
+    assert_access_http(&req, PermissionType::DROP, &slug).await?;
+
+    req.state().fs.write().await
+        .delete_collection(&slug, &*req.state().db.read().await).await?;
+
+    Ok(Response::builder(200).body("success").build())
+}
+
 pub async fn push_records(mut req: Request<AppState>) -> tide::Result {
     let body = req.take_body();
     let mut buf = BufReader::new(body.compat());
@@ -80,23 +108,29 @@ pub async fn push_records(mut req: Request<AppState>) -> tide::Result {
     }
 
     let coll = coll.unwrap();
-    let coll_uuid = coll.read().await.get_uuid().await;
 
     assert_access_http(
         &req, PermissionType::WRITE,
         &coll.read().await.get_slug().await).await?;
 
-    let (dict_id, dict_algo) = coll.read().await.get_dict().await;
-    let dict_id = if let Some(dict_id) = dict_id {
-        Some(dict_id as i64)
-    } else {
-        None
-    };
+    /* zip local file header */
+    if buf.fill_buf().await?.starts_with(b"PK\x03\x04") {
+        let (tmp_dir, _) = req.state().fs.read().await
+            .get_buffer_path(&format!("wacz.{}", Uuid::new_v4()), true).await?;
+        let wacz_path = format!("{}upload.wacz", tmp_dir);
+
+        let ret = async {
+            tokio::io::copy(&mut buf, &mut tokio::fs::File::create(&wacz_path).await?).await?;
+            coll.read().await.import_wacz(&wacz_path, &tmp_dir, Some(&*req.state().db.read().await)).await
+        }.await;
+        tokio::fs::remove_dir_all(&tmp_dir).await?;
+        ret?;
+
+        return Ok(Response::builder(200).body("success").build());
+    }
 
     while let Some(record) = read_record(&mut buf).await? {
-        let cdx = coll.read().await.add_warc(&record).await?;
-        req.state().db.read().await.
-        insert_record(&coll_uuid, &cdx, RECORD_FLAG_ACTIVE, dict_id, dict_algo.as_deref()).await?;
+        coll.read().await.ingest_warc(&record, Some(&*req.state().db.read().await)).await?;
     }
 
     Ok(Response::builder(200).body("success").build())
@@ -171,4 +205,39 @@ pub async fn push_raw_records(mut req: Request<AppState>) -> tide::Result {
     }
 
     Ok(Response::builder(200).body("success").build())
-}
\ No newline at end of file
+}
+
+pub async fn compact_collection(req: Request<AppState>) -> tide::Result {
+    assert_admin_http(&req).await?;
+
+    let coll = req.state().fs.read().await
+        .get_collection(CollID::Uuid(req.param("collection_uuid").unwrap().to_string())).await;
+
+    if coll.is_none() {
+        return Ok(Response::builder(404).body("collection not found").build());
+    }
+    let coll = coll.unwrap();
+
+    coll.write().await.compact(&*req.state().db.read().await).await?;
+
+    Ok(Response::builder(200).body("success").build())
+}
+
+pub async fn export_wacz(req: Request<AppState>) -> tide::Result {
+    let coll = req.state().fs.read().await
+        .get_collection(CollID::Uuid(req.param("collection_uuid").unwrap().to_string())).await;
+
+    if coll.is_none() {
+        return Ok(Response::builder(404).body("collection not found").build());
+    }
+    let coll = coll.unwrap();
+    let coll = coll.read().await;
+    let slug = coll.get_slug().await;
+
+    assert_access_http(&req, PermissionType::READ, &slug).await?;
+
+    let (mut wacz, reader) = wacz_buffer(&*req.state().fs.read().await, &slug).await?;
+    coll.export_wacz(&mut wacz).await?;
+
+    wacz_response(wacz, reader, &format!("{}.wacz", slug))
+}
@@ -0,0 +1,163 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use chrono::{DateTime, NaiveDateTime, SecondsFormat};
+use masstuffy::{constants::MASSTUFFY_DATE_FMT, database::structs::DBWarcRecord};
+use serde_json::json;
+use tide::{Request, Response};
+
+use crate::server_logic::{readable_collection_uuids, AppState};
+
+use super::record_getters::archived_url;
+
+/// flags used in memento uris (replay).
+const MEMENTO_FLAGS: &str = "p";
+
+pub async fn timemap_link(req: Request<AppState>) -> tide::Result {
+    let url = archived_url(&req);
+    let captures = get_permitted_captures(&req, &url).await?;
+    if captures.is_empty() {
+        return Ok(Response::builder(404).body("no capture found").build());
+    }
+
+    let base = base_url(&req);
+    let mut links = vec![
+        format!("<{}>; rel=\"original\"", url),
+        format!("<{}/timegate/{}>; rel=\"timegate\"", base, url),
+        format!("<{}/timemap/link/{}>; rel=\"self\"; type=\"application/link-format\"; from=\"{}\"; until=\"{}\"",
+            base, url,
+            http_date(&captures[0].date), http_date(&captures[captures.len()-1].date)),
+        format!("<{}/timemap/json/{}>; rel=\"timemap\"; type=\"application/json\"", base, url)
+    ];
+
+    for (i, capture) in captures.iter().enumerate() {
+        let rel = match (i == 0, i == captures.len()-1) {
+            (true, true) => "first last memento",
+            (true, false) => "first memento",
+            (false, true) => "last memento",
+            (false, false) => "memento"
+        };
+        links.push(format!("<{}>; rel=\"{}\"; datetime=\"{}\"",
+            memento_uri(&base, capture, &url), rel, http_date(&capture.date)));
+    }
+
+    Ok(Response::builder(200)
+        .body(links.join(",\n") + "\n")
+        .content_type("application/link-format")
+        .build())
+}
+
+pub async fn timemap_json(req: Request<AppState>) -> tide::Result {
+    let url = archived_url(&req);
+    let captures = get_permitted_captures(&req, &url).await?;
+    if captures.is_empty() {
+        return Ok(Response::builder(404).body("no capture found").build());
+    }
+
+    let base = base_url(&req);
+    let memento = |capture: &DBWarcRecord| json!({
+        "datetime": capture.date.and_utc().to_rfc3339_opts(SecondsFormat::Secs, true),
+        "uri": memento_uri(&base, capture, &url)
+    });
+
+    let body = json!({
+        "original_uri": url,
+        "timegate_uri": format!("{}/timegate/{}", base, url),
+        "timemap_uri": {
+            "link_format": format!("{}/timemap/link/{}", base, url),
+            "json_format": format!("{}/timemap/json/{}", base, url)
+        },
+        "mementos": {
+            "first": memento(&captures[0]),
+            "last": memento(&captures[captures.len()-1]),
+            "list": captures.iter().map(memento).collect::<Vec<_>>()
+        }
+    });
+
+    Ok(Response::builder(200)
+        .body(body)
+        .content_type("application/json")
+        .build())
+}
+
+/// redirects to the capture closest to `Accept-Datetime` (the latest one without it).
+pub async fn timegate(req: Request<AppState>) -> tide::Result {
+    let url = archived_url(&req);
+
+    let target = match req.header("Accept-Datetime") {
+        Some(h) => match DateTime::parse_from_rfc2822(h.as_str()) {
+            Ok(d) => Some(d.naive_utc()),
+            Err(_) => return Ok(Response::builder(400).body("invalid Accept-Datetime").build())
+        },
+        None => None
+    };
+
+    let readable: Vec<String> = readable_collection_uuids(&req).await?.into_iter().collect();
+    let closest = req.state().db.read().await.get_closest_capture(&url, target, &readable).await?;
+    let Some(closest) = closest else {
+        return Ok(Response::builder(404)
+            .header("Vary", "accept-datetime")
+            .body("no capture found")
+            .build());
+    };
+
+    let base = base_url(&req);
+    let location = memento_uri(&base, &closest, &url);
+    Ok(Response::builder(302)
+        .header("Location", location.as_str())
+        .header("Vary", "accept-datetime")
+        .header("Memento-Datetime", http_date(&closest.date))
+        .header("Link", format!(
+            "<{}>; rel=\"original\", <{}/timemap/link/{}>; rel=\"timemap\"; type=\"application/link-format\", <{}>; rel=\"memento\"; datetime=\"{}\"",
+            url, base, url, location, http_date(&closest.date)))
+        .build())
+}
+
+/* COMMON LOGIC */
+
+/// captures are addressed by date, copies from the same second are listed once.
+/// timemaps list at most `search_max_limit` captures.
+async fn get_permitted_captures(req: &Request<AppState>, url: &str) -> anyhow::Result<Vec<DBWarcRecord>> {
+    let readable: Vec<String> = readable_collection_uuids(req).await?.into_iter().collect();
+    let limit = req.state().fs.read().await.get_search_max_limit();
+    let mut captures = req.state().db.read().await.get_captures(url, &readable, limit).await?;
+
+    captures.dedup_by_key(|c| c.date.and_utc().timestamp());
+    Ok(captures)
+}
+
+/// `Link` header of mementos (RFC 7089 section 2.2.1).
+pub fn memento_links(req: &Request<AppState>, url: &str) -> String {
+    let base = base_url(req);
+    format!(
+        "<{}>; rel=\"original\", <{}/timegate/{}>; rel=\"timegate\", <{}/timemap/link/{}>; rel=\"timemap\"; type=\"application/link-format\"",
+        url, base, url, base, url)
+}
+
+fn base_url(req: &Request<AppState>) -> String {
+    req.url().origin().ascii_serialization()
+}
+
+fn memento_uri(base: &str, capture: &DBWarcRecord, url: &str) -> String {
+    format!("{}/url/{}/{}/{}", base, MEMENTO_FLAGS, capture.date.format(MASSTUFFY_DATE_FMT), url)
+}
+
+/// RFC 1123 date, as used by `Memento-Datetime`.
+pub fn http_date(date: &NaiveDateTime) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
@@ -19,4 +19,8 @@
 pub mod record_getters;
 pub mod collections;
 pub mod record_search;
-pub mod dictionaries;
\ No newline at end of file
+pub mod dictionaries;
+pub mod record_deletion;
+pub mod memento;
+pub mod tokens;
+pub mod signed_urls;
\ No newline at end of file
@@ -0,0 +1,46 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use masstuffy::permissions::PermissionType;
+use tide::{Request, Response};
+use crate::server_logic::{accessible_collection_uuids, AppState};
+
+/// records of collections the token can't delete from are not looked up,
+/// so they can't be told apart from missing ones.
+pub async fn delete_by_id(req: Request<AppState>) -> tide::Result {
+    let deletable: Vec<String> = accessible_collection_uuids(&req, PermissionType::DELETE).await?
+        .into_iter().collect();
+    let db_rec = req.state().db.read().await
+            .get_record_from_id(req.param("id").unwrap().to_string(), Some(&deletable)).await;
+
+    if let Err(x) = db_rec {
+        return if let Some(sqlx::Error::RowNotFound) = x.downcast_ref::<sqlx::Error>() {
+            Ok(Response::builder(404).body("404 Not Found").build())
+        } else {
+            Err(x.into())
+        }
+    }
+    let db_rec = db_rec.unwrap();
+
+    req.state().fs.read().await.delete_record(
+        &db_rec.collection,
+        &db_rec.identifier,
+        &*req.state().db.read().await).await?;
+
+    Ok(Response::builder(200).body("success").build())
+}
@@ -17,28 +17,51 @@
 **/
 
 use std::io::Write;
-use masstuffy::{database::structs::DBWarcRecord, filesystem::CollID, permissions::PermissionType};
-use tide::{Request, Response};
-use crate::server_logic::{assert_access_http, AppState};
+use anyhow::anyhow;
+use masstuffy::{constants::MASSTUFFY_DATE_FMT, database::structs::DBWarcRecord, filesystem::CollID, permissions::PermissionType, signed_urls::{sign_path, strip_signature, UrlSignature}, warc::{http::{is_http_response, HttpResponse}, revisit::{is_identical_payload_revisit, resolve_revisit}, rewrite::{DocumentKind, LinkRewriter}, WarcRecord}};
+use tide::{Request, Response, StatusCode};
+use crate::server_logic::{assert_access_http, readable_collection_uuids, AppState};
+
+use super::memento::{http_date, memento_links};
 
 /* FRONTEND HANDLERS */
 
 pub async fn get_by_id(req: Request<AppState>) -> tide::Result {
+    let collections = permitted_collections(&req).await?;
     let db_rec = req.state().db.read().await
-            .get_record_from_id(req.param("id").unwrap().to_string()).await?;
+            .get_record_from_id(req.param("id").unwrap().to_string(), Some(&collections)).await?;
     
     unified_handler(req, db_rec).await
 }
 
 pub async fn get_by_url(req: Request<AppState>) -> tide::Result {
-    let url = req.param("url").unwrap().to_string();
+    let url = archived_url(&req);
+    let collections = permitted_collections(&req).await?;
     let db_rec = req.state().db.read().await
             .get_record_from_uri(
                 &req.param("date")?.to_string(),
-                &url).await?;
-    
-    // TODO: redirect on different timestamp (might be disabled with flag)
-    unified_handler(req, db_rec).await
+                &url, Some(&collections)).await?;
+
+    /* point clients to the capture they actually get */
+    let date = db_rec.date.format(MASSTUFFY_DATE_FMT).to_string();
+    if (parse_flags(&req) & RECORD_FLAGS_NO_REDIRECT) == 0 && date != req.param("date")? {
+        assert_record_access(&req, &db_rec).await?;
+        let mut location = format!("/url/{}/{}/{}", req.param("flags")?, date, url);
+        if let Some(signature) = url_signature(&req) {
+            /* the signature covers the requested date */
+            location = sign_path(
+                req.state().fs.read().await.get_secret_key()?,
+                &location, &signature.collection, signature.expires, Some(&signature.flags))?;
+        }
+        return Ok(Response::builder(302)
+            .header("Location", location)
+            .build());
+    }
+
+    let links = memento_links(&req, &url);
+    let mut res = unified_handler(req, db_rec).await?;
+    res.append_header("Link", links);
+    Ok(res)
 }
 
 /* COMMON LOGIC */
@@ -46,29 +69,100 @@ pub async fn get_by_url(req: Request<AppState>) -> tide::Result {
 const RECORD_FLAGS_WARC_HEADER: u64 = 1<<0;
 const RECORD_FLAGS_FORCE_DOWNLOAD: u64 = 1<<1;
 const RECORD_FLAGS_RAW: u64 = 1<<2;
+const RECORD_FLAGS_NO_REDIRECT: u64 = 1<<3;
+const RECORD_FLAGS_REPLAY: u64 = 1<<4;
+const RECORD_FLAGS_DECODE: u64 = 1<<5;
+const RECORD_FLAGS_REWRITE: u64 = 1<<6;
 
-async fn unified_handler(req: Request<AppState>, record: DBWarcRecord) -> tide::Result {
-    /* check access */
-    let coll_slug = req.state().fs.read().await.
-        get_collection(CollID::Uuid(record.collection.clone())).await.
-        unwrap().read().await.get_slug().await; // if we found a record, it is unlikely we will not find the associated collection.
-
-    assert_access_http(
-        &req, PermissionType::READ,
-        &coll_slug).await?;
-
-    /* convert char flags to bit flags */
+/// converts char flags to bit flags
+fn parse_flags(req: &Request<AppState>) -> u64 {
     let mut flags: u64 = 0;
     for c in req.param("flags").unwrap().chars().into_iter() {
         flags |= match c {
             'h' => RECORD_FLAGS_WARC_HEADER,
             'd' => RECORD_FLAGS_FORCE_DOWNLOAD,
             'r' => RECORD_FLAGS_RAW,
+            'n' => RECORD_FLAGS_NO_REDIRECT,
+            'p' => RECORD_FLAGS_REPLAY,
+            'c' => RECORD_FLAGS_DECODE,
+            'w' => RECORD_FLAGS_REWRITE|RECORD_FLAGS_REPLAY|RECORD_FLAGS_DECODE,
             _ => 0
         }
     }
+    flags
+}
+
+/// the wildcard doesn't capture the query string, signature parameters are left out of it.
+pub fn archived_url(req: &Request<AppState>) -> String {
+    let url = req.param("url").unwrap().to_string();
+    match req.url().query().map(|q| strip_signature(q).0) {
+        Some(query) if !query.is_empty() => format!("{}?{}", url, query),
+        _ => url
+    }
+}
+
+fn url_signature(req: &Request<AppState>) -> Option<UrlSignature> {
+    strip_signature(req.url().query()?).1
+}
+
+/// signed urls only serve records of the signed collection.
+fn signed_collection(req: &Request<AppState>) -> Option<Vec<String>> {
+    url_signature(req).map(|s| vec![s.collection])
+}
+
+/// collections records are looked up in: the signed one or the readable ones,
+/// so identifiers and captures of other collections can't be told apart from missing ones.
+async fn permitted_collections(req: &Request<AppState>) -> anyhow::Result<Vec<String>> {
+    Ok(match signed_collection(req) {
+        Some(collections) => collections,
+        None => readable_collection_uuids(req).await?.into_iter().collect()
+    })
+}
+
+/// what signed urls point to (see `signed_urls::parse_record_path`).
+fn signed_resource(req: &Request<AppState>) -> String {
+    match req.param("id") {
+        Ok(id) => format!("/id/{}", id),
+        Err(_) => format!("/url/{}/{}", req.param("date").unwrap(), archived_url(req))
+    }
+}
+
+/// signed urls are an alternative to the token.
+async fn assert_record_access(req: &Request<AppState>, record: &DBWarcRecord) -> anyhow::Result<()> {
+    if let Some(signature) = url_signature(req) {
+        let fs = req.state().fs.read().await;
+        return if signature.verify(fs.get_secret_key()?, &signed_resource(req), req.param("flags").unwrap())
+            && signature.collection == record.collection {
+            Ok(())
+        } else {
+            Err(anyhow!("invalid or expired signature"))
+        };
+    }
+
+    let coll_slug = req.state().fs.read().await.
+        get_collection(CollID::Uuid(record.collection.clone())).await.
+        unwrap().read().await.get_slug().await; // if we found a record, it is unlikely we will not find the associated collection.
+
+    assert_access_http(
+        req, PermissionType::READ,
+        &coll_slug).await
+}
+
+async fn unified_handler(req: Request<AppState>, record: DBWarcRecord) -> tide::Result {
+    assert_record_access(&req, &record).await?;
+
+    let flags = parse_flags(&req);
 
     if (flags & RECORD_FLAGS_RAW) != 0 {
+        /* the stored revisit lacks the payload and the resolved response has no stored bytes */
+        if record.r#type == "revisit" {
+            let rec = req.state().fs.read().await.get_record(
+                &record.collection, &record.filename, record.offset).await?;
+            if rec.as_ref().is_some_and(is_identical_payload_revisit) {
+                return Err(tide::Error::from_str(400, "raw mode is not available for revisit records, fetch them without `r`"));
+            }
+        }
+
         let raw_record = req.state().fs.read().await.get_raw_record(
             &record.collection,
             &record.filename,
@@ -76,6 +170,7 @@ async fn unified_handler(req: Request<AppState>, record: DBWarcRecord) -> tide::
             record.raw_size as usize).await?;
         return if let Some(raw_record) = raw_record {
             Ok(Response::builder(200)
+                .header("Memento-Datetime", http_date(&record.date))
                 .header("Warc-Dictionary-Id", record.dict_id.map(|a| format!("{}", a)).unwrap_or("".to_string()))
                 .body(raw_record)
                 .build())
@@ -84,11 +179,25 @@ async fn unified_handler(req: Request<AppState>, record: DBWarcRecord) -> tide::
         }
     }
 
-    let mut ret = Response::builder(200);
+    let mut ret = Response::builder(200)
+        .header("Memento-Datetime", http_date(&record.date));
     let mut tmp_body: Vec<u8> = Vec::new();
-    let rec = req.state().fs.read().await.get_record(
+    let mut rec = req.state().fs.read().await.get_record(
         &record.collection, &record.filename, record.offset).await?.unwrap();
 
+    if is_identical_payload_revisit(&rec) {
+        rec = resolve_payload(&req, &record, &rec).await?;
+    }
+
+    if (flags&RECORD_FLAGS_REPLAY) != 0 && is_http_response(&rec) {
+        let rewrite_prefix = if (flags&RECORD_FLAGS_REWRITE) != 0 {
+            Some(format!("/url/{}/{}/", req.param("flags")?, record.date.format(MASSTUFFY_DATE_FMT)))
+        } else {
+            None
+        };
+        return replay_response(&record, &rec, (flags&RECORD_FLAGS_DECODE) != 0, rewrite_prefix).await;
+    }
+
     if (flags&RECORD_FLAGS_WARC_HEADER) != 0 {
         rec.write_headers(&mut tmp_body).unwrap();
     }
@@ -123,4 +232,89 @@ async fn unified_handler(req: Request<AppState>, record: DBWarcRecord) -> tide::
     }
 
     Ok(ret.body(tmp_body).build())
-}
\ No newline at end of file
+}
+
+/// rebuilds the response a revisit stands for, so readers never see it.
+pub async fn resolve_payload(req: &Request<AppState>, record: &DBWarcRecord, revisit: &WarcRecord) -> tide::Result<WarcRecord> {
+    let collections = permitted_collections(req).await?;
+    let mut original = None;
+    {
+        /* identifiers are not unique across collections, look for the payload in the same collection first */
+        let db = req.state().db.read().await;
+        if let Some(digest) = &record.payload_digest {
+            original = db.get_payload_original(&record.collection, digest).await?;
+        }
+        if original.is_none() && let Some(id) = revisit.get_header("WARC-Refers-To") {
+            original = db.get_record_from_id(
+                id.trim_matches(|x| "<>".contains(x)).to_string(),
+                Some(&collections)).await.ok()
+                .filter(|o| o.r#type == "response");
+        }
+    }
+
+    let Some(original) = original else {
+        return Err(tide::Error::from_str(404, "revisited payload not found"));
+    };
+    assert_record_access(req, &original).await?;
+
+    let original = req.state().fs.read().await.get_record(
+        &original.collection, &original.filename, original.offset).await?.unwrap();
+    Ok(resolve_revisit(revisit, &original)?)
+}
+
+/// resources of replayed pages can only come from the repository (inline styles are archived as well).
+/// archived scripts share the repository's origin, so pages are sandboxed (unique origin, no scripts nor forms).
+const REPLAY_CSP: &str = "sandbox; default-src 'self' 'unsafe-inline' data: blob:; script-src 'none'";
+
+/// answers with the archived http response instead of the record.
+async fn replay_response(record: &DBWarcRecord, rec: &WarcRecord, decode: bool, rewrite_prefix: Option<String>) -> tide::Result {
+    let mut http = HttpResponse::parse(rec.get_body())?;
+    if decode {
+        http.decode_content_encoding().await?;
+    }
+
+    if let (Some(prefix), Some(url)) = (rewrite_prefix, &record.uri) {
+        rewrite_links(&mut http, url, prefix)?;
+    }
+
+    /* the original policy would block resources served by the repository,
+       ours keeps archived pages from reaching the live web */
+    http.headers.retain(|(k, _)| !k.to_ascii_lowercase().starts_with("content-security-policy"));
+
+    /* tide only knows registered codes, others (e.g. 999) are answered as 200 */
+    let mut ret = match StatusCode::try_from(http.status) {
+        Ok(status) => Response::new(status),
+        Err(_) => {
+            let mut ret = Response::new(StatusCode::Ok);
+            ret.insert_header("Archived-Status", http.status.to_string());
+            ret
+        }
+    };
+    for (k, v) in http.safe_headers() {
+        if !k.is_empty() && k.is_ascii() && v.is_ascii() {
+            ret.append_header(k.as_str(), v.as_str());
+        }
+    }
+    ret.insert_header("Content-Security-Policy", REPLAY_CSP);
+    ret.insert_header("Memento-Datetime", http_date(&record.date));
+    ret.set_body(http.body); // keeps the archived Content-Type
+
+    Ok(ret)
+}
+
+/// points links of the page (and redirects) back to the repository.
+fn rewrite_links(http: &mut HttpResponse, url: &str, prefix: String) -> anyhow::Result<()> {
+    let mut rewriter = LinkRewriter::new(url, prefix)?;
+
+    if let Some(kind) = http.get_header("Content-Type").and_then(DocumentKind::from_mime) {
+        http.body = rewriter.rewrite(&kind, &http.body);
+    }
+
+    for (k, v) in http.headers.iter_mut() {
+        if k.eq_ignore_ascii_case("Location") && let Some(location) = rewriter.rewrite_url(v) {
+            *v = location;
+        }
+    }
+
+    Ok(())
+}
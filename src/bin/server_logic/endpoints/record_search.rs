@@ -16,41 +16,103 @@
  *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
 **/
 
-use masstuffy::{constants::MASSTUFFY_DATE_FMT, database::structs::DBWarcRecord, warc::massaged_url::Match};
+use std::{fmt::Display, str::FromStr};
+
+use masstuffy::{constants::MASSTUFFY_DATE_FMT, database::{structs::DBWarcRecord, SearchCursor, SearchFilters}, filesystem::CollID, permissions::PermissionType, utils::dates::parse_date_prefix, warc::{cdxj::{sort_entries, CDXJEntry, CDX11_HEADER}, massaged_url::Match, revisit::is_identical_payload_revisit}};
 use serde::Serialize;
 use tide::{Request, Response};
 
-use crate::server_logic::AppState;
+use crate::server_logic::{assert_access_http, readable_collection_uuids, wacz_buffer, wacz_response, AppState};
+
+use super::record_getters::resolve_payload;
+
+/// records per page when `limit` is not given.
+const SEARCH_DEFAULT_LIMIT: i64 = 100;
 
-// TODO: pagination
 pub async fn search_record(req: Request<AppState>) -> tide::Result {
-    let mut host = Match::None;
-    let mut path = Match::None;
-    let mut port: Option<u16> = None;
+    let mut filters = SearchFilters::default();
+    let mut collection: Option<String> = None;
+    let mut after: Option<SearchCursor> = None;
+    let mut limit = SEARCH_DEFAULT_LIMIT;
+    let mut format = "json".to_string();
 
     for p in req.url().query_pairs() {
         match p.0.as_ref() {
-            "host" => host = Match::PartialMatch(p.1.to_string()),
-            "host_exact" => host = Match::ExactMatch(p.1.to_string()),
-            "path" => path = Match::PartialMatch(p.1.to_string()),
-            "path_exact" => host = Match::ExactMatch(p.1.to_string()),
-            "port" => port = Some(p.1.parse::<u16>().unwrap_or(0)),
+            "host" => filters.host = Match::PartialMatch(p.1.to_string()),
+            "host_exact" => filters.host = Match::ExactMatch(p.1.to_string()),
+            "path" => filters.path = Match::PartialMatch(p.1.to_string()),
+            "path_exact" => filters.path = Match::ExactMatch(p.1.to_string()),
+            "port" => filters.port = Some(p.1.parse::<u16>().unwrap_or(0)),
+            "from" => filters.from = Some(parse_date_prefix(&p.1, false).map_err(|e| tide::Error::new(400, e))?),
+            "to" => filters.to = Some(parse_date_prefix(&p.1, true).map_err(|e| tide::Error::new(400, e))?),
+            "type" => filters.record_type = Some(p.1.to_string()),
+            "collection" => collection = Some(p.1.to_string()),
+            "mime" => filters.mime = Some(p.1.to_lowercase()),
+            "status" => filters.status = Some(parse_param("status", &p.1)?),
+            "after" => after = Some(parse_param("after", &p.1)?),
+            "limit" => limit = parse_param("limit", &p.1)?,
+            "format" => format = p.1.to_string(),
             _ => {}
         }
     }
 
-    let records = req.state().db.read().await
-        .search(host, port, path, 100).await?;
+    if let Some(slug) = collection {
+        let Some(coll) = req.state().fs.read().await.get_collection(CollID::Slug(slug.clone())).await else {
+            return Ok(Response::builder(404).body("collection not found").build());
+        };
+        assert_access_http(&req, PermissionType::READ, &slug).await?;
+        filters.collection = Some(coll.read().await.get_uuid().await);
+    }
+
+    /* filtered in the query so pages stay full */
+    filters.collections = Some(readable_collection_uuids(&req).await?.into_iter().collect());
+
+    let limit = limit.clamp(1, req.state().fs.read().await.get_search_max_limit());
+    /* cdx indexes are sorted across pages */
+    let records = if format == "cdxj" || format == "cdx11" {
+        req.state().db.read().await.search_surt_ordered(filters, after.as_ref(), limit).await?
+    } else {
+        req.state().db.read().await.search(filters, after.as_ref(), limit).await?
+    };
+
+    /* a full page may be followed by another one */
+    let next = if records.len() as i64 == limit {
+        records.last().map(SearchCursor::after)
+    } else {
+        None
+    };
+
+    let mut res = format_response(&req, records, &format).await?;
+    if let Some(next) = next {
+        res.insert_header("Link", format!("<{}>; rel=\"next\"", next_page_url(&req, next)));
+    }
+    Ok(res)
+}
+
+/// parses a query parameter, answers 400 if it is invalid.
+fn parse_param<T: FromStr>(name: &str, value: &str) -> tide::Result<T> where T::Err: Display {
+    value.parse::<T>().map_err(|e| tide::Error::from_str(400, format!("invalid {}: {}", name, e)))
+}
+
+/// the same search, starting after the record `after`.
+fn next_page_url(req: &Request<AppState>, after: SearchCursor) -> String {
+    let mut url = req.url().clone();
+    let pairs: Vec<(String, String)> = url.query_pairs()
+        .filter(|(k, _)| k != "after")
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    url.query_pairs_mut().clear().extend_pairs(pairs).append_pair("after", &after.to_string());
 
-    
-    format_response(records, req.param("format").as_deref().unwrap_or("json")).await
+    format!("{}?{}", url.path(), url.query().unwrap_or_default())
 }
 
 /* FORMATS */
 
-async fn format_response(records: Vec<DBWarcRecord>, format: &str) -> tide::Result {
+async fn format_response(req: &Request<AppState>, records: Vec<DBWarcRecord>, format: &str) -> tide::Result {
     match format {
         "json" => format_response_json(records).await,
+        "cdxj" | "cdx11" => format_response_cdx(records, format == "cdx11").await,
+        "wacz" => format_response_wacz(req, records).await,
         _ => format_response_json(records).await,
     }
 }
@@ -61,7 +123,9 @@ struct RecordSearchJson{
     identifier: String,
     r#type: String,
     collection: String,
-    date: String
+    date: String,
+    status: Option<i16>,
+    mime: Option<String>
 }
 
 async fn format_response_json(records: Vec<DBWarcRecord>) -> tide::Result {
@@ -71,7 +135,9 @@ async fn format_response_json(records: Vec<DBWarcRecord>) -> tide::Result {
             uri: d.uri.clone(),
             r#type: d.r#type.clone(),
             collection: d.collection.clone(),
-            date: d.date.format(MASSTUFFY_DATE_FMT).to_string()
+            date: d.date.format(MASSTUFFY_DATE_FMT).to_string(),
+            status: d.status,
+            mime: d.mime.clone()
         }
     }).collect();
 
@@ -79,4 +145,57 @@ async fn format_response_json(records: Vec<DBWarcRecord>) -> tide::Result {
         .body(serde_json::to_string(&ret_records)?)
         .content_type("application/json")
         .build())
-}
\ No newline at end of file
+}
+
+async fn format_response_cdx(records: Vec<DBWarcRecord>, cdx11: bool) -> tide::Result {
+    let mut entries: Vec<CDXJEntry> = Vec::new();
+    for r in &records {
+        let Some(url) = &r.uri else {
+            continue;
+        };
+
+        entries.push(CDXJEntry::new(
+            url, &r.date.format(MASSTUFFY_DATE_FMT).to_string(),
+            format!("{}/{}", r.collection, r.filename), r.offset as u64, r.raw_size as u64)?
+            .with_details(&r.r#type, r.mime.clone(), r.status.map(|s| s as u16), r.payload_digest.clone()));
+    }
+
+    sort_entries(&mut entries);
+
+    let mut body = String::new();
+    if cdx11 {
+        body.push_str(CDX11_HEADER);
+        body.push('\n');
+    }
+    for entry in &entries {
+        body.push_str(&if cdx11 {entry.to_cdx11()} else {entry.to_cdxj()});
+        body.push('\n');
+    }
+
+    Ok(Response::builder(200)
+        .body(body)
+        .content_type(if cdx11 {"text/x-cdx"} else {"text/x-cdxj"})
+        .build())
+}
+
+async fn format_response_wacz(req: &Request<AppState>, records: Vec<DBWarcRecord>) -> tide::Result {
+    let (mut wacz, reader) = wacz_buffer(&*req.state().fs.read().await, "search results").await?;
+    for r in &records {
+        let Some(mut record) = req.state().fs.read().await
+            .get_record(&r.collection, &r.filename, r.offset).await? else {
+            continue;
+        };
+
+        /* the original may not be part of the results */
+        if is_identical_payload_revisit(&record) {
+            match resolve_payload(req, r, &record).await {
+                Ok(response) => record = response,
+                Err(x) if x.status() == 404 => {},
+                Err(x) => return Err(x)
+            }
+        }
+        wacz.add_record(&record).await?;
+    }
+
+    wacz_response(wacz, reader, "search.wacz")
+}
@@ -0,0 +1,86 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use anyhow::anyhow;
+use chrono::{DateTime, SecondsFormat, Utc};
+use masstuffy::{filesystem::CollID, signed_urls::{parse_record_path, sign_path}};
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response};
+use crate::server_logic::{request_token, AppState};
+
+#[derive(Deserialize)]
+struct SignParams {
+    /// `/id/:flags/:id` or `/url/:flags/:date/*url`
+    path: String,
+    /// seconds, `signed_url_max_lifetime` by default
+    expires_in: Option<i64>,
+    /// flags the url may be requested with, those of `path` by default
+    flags: Option<String>
+}
+
+/// signs a record url, so it can be fetched without the token until it expires.
+pub async fn sign_url(mut req: Request<AppState>) -> tide::Result {
+    let params: SignParams = req.body_json().await?;
+
+    /* anonymous clients have nothing to share */
+    let Some(token) = request_token(&req).await? else {
+        return Err(anyhow!("forbidden").into());
+    };
+
+    let fs = req.state().fs.read().await;
+    let db = req.state().db.read().await;
+
+    let max_lifetime = fs.get_signed_url_max_lifetime();
+    let expires_in = params.expires_in.unwrap_or(max_lifetime);
+    if expires_in <= 0 || expires_in > max_lifetime {
+        return Err(tide::Error::from_str(400, format!("expires_in must be between 1 and {}", max_lifetime)));
+    }
+
+    let Some((_, resource)) = parse_record_path(&params.path) else {
+        return Err(tide::Error::from_str(400, "path must be /id/:flags/:id or /url/:flags/:date/*url"));
+    };
+    /* only look for the record in the collections the token can read */
+    let mut readable = Vec::new();
+    for slug in fs.get_collection_list().await {
+        if token.read_perms.check(&slug) && let Some(coll) = fs.get_collection(CollID::Slug(slug)).await {
+            readable.push(coll.read().await.get_uuid().await);
+        }
+    }
+
+    let record = if let Some(id) = resource.strip_prefix("/id/") {
+        db.get_record_from_id(id.to_string(), Some(&readable)).await
+    } else {
+        let (date, url) = resource["/url/".len()..].split_once('/').unwrap();
+        db.get_record_from_uri(&date.to_string(), &url.to_string(), Some(&readable)).await
+    };
+    let Ok(record) = record else {
+        return Ok(Response::builder(404).body("record not found").build());
+    };
+
+    let expires = Utc::now().timestamp() + expires_in;
+    let url = sign_path(fs.get_secret_key()?, &params.path, &record.collection, expires, params.flags.as_deref())
+        .map_err(|e| tide::Error::from_str(400, e.to_string()))?;
+
+    Ok(Response::builder(200)
+        .body(json!({
+            "url": url,
+            "expires_at": DateTime::from_timestamp(expires, 0).unwrap().to_rfc3339_opts(SecondsFormat::Secs, true)
+        }))
+        .build())
+}
@@ -0,0 +1,243 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use masstuffy::permissions::{generate_token, hash_token, TokenInfo, TokenPermission};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::json;
+use tide::{Request, Response};
+use uuid::Uuid;
+use crate::server_logic::{assert_admin_http, AppState};
+
+/// a permission, as in `config.json` (`kind`: none, any, list or prefix).
+#[derive(Serialize, Deserialize)]
+struct PermissionJson {
+    kind: String,
+    #[serde(default)]
+    perms: String
+}
+
+impl PermissionJson {
+    fn from_permission(perm: &TokenPermission) -> Self {
+        PermissionJson{kind: perm.get_perms_kind_str().to_string(), perms: perm.get_perms()}
+    }
+
+    fn to_permission(&self) -> tide::Result<TokenPermission> {
+        match self.kind.as_str() {
+            "none" | "any" | "list" | "prefix" => Ok(TokenPermission::from_fs_perms(&self.kind, &self.perms)),
+            kind => Err(tide::Error::from_str(400, format!("unknown permission kind: {}", kind)))
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TokenJson {
+    id: String,
+    /// only known on creation and rotation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    comment: String,
+    admin: bool,
+    expires_at: Option<String>,
+    last_used_at: Option<String>,
+    read: PermissionJson,
+    write: PermissionJson,
+    delete: PermissionJson,
+    create_coll: PermissionJson,
+    delete_coll: PermissionJson,
+    list_coll: PermissionJson
+}
+
+impl TokenJson {
+    fn from_token(token: &TokenInfo) -> Self {
+        TokenJson{
+            id: token.id.clone(),
+            token: None,
+            comment: token.comment.clone(),
+            admin: token.admin,
+            expires_at: token.expires_at.map(|d| d.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            last_used_at: token.last_used_at.map(|d| d.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            read: PermissionJson::from_permission(&token.read_perms),
+            write: PermissionJson::from_permission(&token.write_perms),
+            delete: PermissionJson::from_permission(&token.delete_perms),
+            create_coll: PermissionJson::from_permission(&token.create_coll_perms),
+            delete_coll: PermissionJson::from_permission(&token.delete_coll_perms),
+            list_coll: PermissionJson::from_permission(&token.list_coll_perms)
+        }
+    }
+}
+
+/// fields left out are not granted on creation and left as-is on update.
+#[derive(Deserialize)]
+struct TokenParams {
+    comment: Option<String>,
+    admin: Option<bool>,
+    /// RFC 3339 date, `null` removes the expiry
+    #[serde(default, deserialize_with = "deserialize_some")]
+    expires_at: Option<Option<String>>,
+    read: Option<PermissionJson>,
+    write: Option<PermissionJson>,
+    delete: Option<PermissionJson>,
+    create_coll: Option<PermissionJson>,
+    delete_coll: Option<PermissionJson>,
+    list_coll: Option<PermissionJson>
+}
+
+/// tells `null` (`Some(None)`) apart from missing fields (`None`).
+fn deserialize_some<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Option<String>>, D::Error> {
+    Option::<String>::deserialize(deserializer).map(Some)
+}
+
+impl TokenParams {
+    fn apply(self, token: &mut TokenInfo) -> tide::Result<()> {
+        if let Some(comment) = self.comment {
+            token.comment = comment;
+        }
+        if let Some(expires_at) = self.expires_at {
+            token.expires_at = match expires_at {
+                Some(date) => Some(DateTime::parse_from_rfc3339(&date)
+                    .map_err(|e| tide::Error::from_str(400, format!("invalid expires_at: {}", e)))?
+                    .with_timezone(&Utc)),
+                None => None
+            };
+        }
+        if let Some(admin) = self.admin {
+            token.admin = admin;
+        }
+        if let Some(read) = self.read {
+            token.read_perms = read.to_permission()?;
+        }
+        if let Some(write) = self.write {
+            token.write_perms = write.to_permission()?;
+        }
+        if let Some(delete) = self.delete {
+            token.delete_perms = delete.to_permission()?;
+        }
+        if let Some(create_coll) = self.create_coll {
+            token.create_coll_perms = create_coll.to_permission()?;
+        }
+        if let Some(delete_coll) = self.delete_coll {
+            token.delete_coll_perms = delete_coll.to_permission()?;
+        }
+        if let Some(list_coll) = self.list_coll {
+            token.list_coll_perms = list_coll.to_permission()?;
+        }
+        Ok(())
+    }
+}
+
+pub async fn list_tokens(req: Request<AppState>) -> tide::Result {
+    assert_admin_http(&req).await?;
+
+    let tokens: Vec<TokenJson> = req.state().db.read().await
+        .get_all_permissions().await?
+        .iter().map(TokenJson::from_token).collect();
+
+    Ok(Response::builder(200)
+        .body(json!(tokens))
+        .build())
+}
+
+pub async fn create_token(mut req: Request<AppState>) -> tide::Result {
+    assert_admin_http(&req).await?;
+    let params: TokenParams = req.body_json().await?;
+
+    let plain_token = generate_token();
+    let mut token = TokenInfo{
+        id: Uuid::new_v4().to_string(),
+        token_hash: hash_token(req.state().fs.read().await.get_secret_key()?, &plain_token),
+        comment: String::new(),
+        read_perms: TokenPermission::None,
+        write_perms: TokenPermission::None,
+        delete_perms: TokenPermission::None,
+        admin: false,
+        create_coll_perms: TokenPermission::None,
+        delete_coll_perms: TokenPermission::None,
+        list_coll_perms: TokenPermission::None,
+        expires_at: None,
+        last_used_at: None};
+    params.apply(&mut token)?;
+
+    let mut body = TokenJson::from_token(&token);
+    body.token = Some(plain_token);
+    req.state().db.read().await.create_permissions(token).await?;
+
+    Ok(Response::builder(201)
+        .body(json!(body))
+        .build())
+}
+
+pub async fn get_token(req: Request<AppState>) -> tide::Result {
+    assert_admin_http(&req).await?;
+
+    match req.state().db.read().await.get_permissions_by_id(req.param("token_id")?).await? {
+        Some(token) => Ok(Response::builder(200)
+            .body(json!(TokenJson::from_token(&token)))
+            .build()),
+        None => Ok(Response::builder(404).body("token not found").build())
+    }
+}
+
+pub async fn update_token(mut req: Request<AppState>) -> tide::Result {
+    assert_admin_http(&req).await?;
+    let params: TokenParams = req.body_json().await?;
+
+    let db = req.state().db.read().await;
+    let Some(mut token) = db.get_permissions_by_id(req.param("token_id")?).await? else {
+        return Ok(Response::builder(404).body("token not found").build());
+    };
+    params.apply(&mut token)?;
+    db.update_permissions(&token).await?;
+
+    Ok(Response::builder(200)
+        .body(json!(TokenJson::from_token(&token)))
+        .build())
+}
+
+/// replaces the token, keeping its identifier and permissions.
+pub async fn rotate_token(req: Request<AppState>) -> tide::Result {
+    assert_admin_http(&req).await?;
+
+    let plain_token = generate_token();
+    let token_hash = hash_token(req.state().fs.read().await.get_secret_key()?, &plain_token);
+
+    let db = req.state().db.read().await;
+    let id = req.param("token_id")?;
+    if db.set_token_hash(id, &token_hash).await? == 0 {
+        return Ok(Response::builder(404).body("token not found").build());
+    }
+    let Some(token) = db.get_permissions_by_id(id).await? else {
+        return Ok(Response::builder(404).body("token not found").build());
+    };
+
+    let mut body = TokenJson::from_token(&token);
+    body.token = Some(plain_token);
+    Ok(Response::builder(200)
+        .body(json!(body))
+        .build())
+}
+
+pub async fn revoke_token(req: Request<AppState>) -> tide::Result {
+    assert_admin_http(&req).await?;
+
+    if req.state().db.read().await.delete_permissions(req.param("token_id")?).await? == 0 {
+        return Ok(Response::builder(404).body("token not found").build());
+    }
+
+    Ok(Response::builder(200).body("success").build())
+}
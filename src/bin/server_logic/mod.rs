@@ -15,14 +15,19 @@
  * 
  *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
 **/
-use std::sync::Arc;
+use std::{collections::HashSet, io::BufWriter, sync::Arc};
+use anyhow::anyhow;
 
-use masstuffy::{database::DBManager, filesystem::{self, FileSystem}, permissions::{assert_access, PermissionType}};
+use masstuffy::{database::DBManager, filesystem::{self, FileSystem}, permissions::{lookup_token, PermissionType, TokenInfo}, warc::wacz::WaczWriter};
+use log::{error, info};
 use serde::Serialize;
 use tide::{utils::After, Body, Request, Response};
 use tokio::sync::RwLock;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+use uuid::Uuid;
 
 mod endpoints;
+mod rate_limit;
 
 
 #[derive(Clone)]
@@ -52,12 +57,30 @@ pub async fn main() {
     
     let listen_addr = fs.get_listen_addr();
     let database_conn = fs.get_database_conn_string();
+    let db = DBManager::new(&database_conn);
+
+    // tokens stored before they were hashed
+    match fs.get_secret_key() {
+        Ok(secret_key) => match db.hash_plain_tokens(secret_key).await {
+            Ok(0) => {},
+            Ok(n) => info!("{} plain token(s) hashed", n),
+            Err(e) => error!("unable to hash plain tokens: {:?}", e)
+        },
+        Err(e) => error!("tokens are disabled: {:?}", e)
+    }
+
+    if let Err(e) = fs.resync_databases(&db).await {
+        error!("unable to resync database records: {:?}", e);
+    }
 
     let state = AppState{
         fs: Arc::new(RwLock::new(fs)),
-        db: Arc::new(RwLock::new(DBManager::new(&database_conn)))
+        db: Arc::new(RwLock::new(db))
     };
     
+    let rate_limiter = rate_limit::RateLimiter::default();
+    rate_limiter.spawn_flusher(state.clone());
+
     let mut app = tide::with_state(state);
 
     app.with(After(|mut res: Response| async {
@@ -66,31 +89,122 @@ pub async fn main() {
         }
         Ok(res)
     }));
+    app.with(rate_limiter);
 
     app.at("/").get(server_status_handler);
     app.at("/id/:flags/:id").get(endpoints::record_getters::get_by_id);
     app.at("/url/:flags/:date/*url").get(endpoints::record_getters::get_by_url);
+    app.at("/timegate/*url").get(endpoints::memento::timegate);
+    app.at("/timemap/link/*url").get(endpoints::memento::timemap_link);
+    app.at("/timemap/json/*url").get(endpoints::memento::timemap_json);
+    app.at("/id/:id").delete(endpoints::record_deletion::delete_by_id);
     app.at("/collections").get(endpoints::collections::list_collections);
     app.at("/search").get(endpoints::record_search::search_record);
     app.at("/collections").post(endpoints::collections::create_collection);
+    app.at("/collection/:collection_uuid").delete(endpoints::collections::delete_collection);
     app.at("/collection/:collection_uuid/records").post(endpoints::collections::push_records);
     app.at("/collection/:collection_uuid/raw_records").post(endpoints::collections::push_raw_records);
+    app.at("/collection/:collection_uuid/compact").post(endpoints::collections::compact_collection);
+    app.at("/collection/:collection_uuid/wacz").get(endpoints::collections::export_wacz);
     app.at("/dictionary/:dict_id").get(endpoints::dictionaries::get_dictionary);
+    app.at("/tokens").get(endpoints::tokens::list_tokens);
+    app.at("/tokens").post(endpoints::tokens::create_token);
+    app.at("/tokens/:token_id").get(endpoints::tokens::get_token);
+    app.at("/tokens/:token_id").patch(endpoints::tokens::update_token);
+    app.at("/tokens/:token_id").delete(endpoints::tokens::revoke_token);
+    app.at("/tokens/:token_id/rotate").post(endpoints::tokens::rotate_token);
+    app.at("/sign").post(endpoints::signed_urls::sign_url);
     app.listen(listen_addr).await.expect("server error");
 }
 
-async fn assert_access_http(req: &Request<AppState>, permtype: PermissionType, coll_slug: &String) -> anyhow::Result<()> {
-    let token_header = req.header("Authorization");
-    let token = // TODO: is there a proper way to do it?
-    if let Some(h) = token_header {
-        &h.as_str()[7..] // remove "Bearer "
+fn http_token(req: &Request<AppState>) -> &str {
+    // TODO: is there a proper way to do it?
+    if let Some(h) = req.header("Authorization") {
+        h.as_str().strip_prefix("Bearer ").unwrap_or("")
     } else {
         ""
-    };
+    }
+}
+
+async fn assert_access_http(req: &Request<AppState>, permtype: PermissionType, coll_slug: &String) -> anyhow::Result<()> {
+    if !token_info(req).await?.has_access(permtype, coll_slug) {
+        Err(anyhow!("forbidden"))
+    } else {
+        Ok(())
+    }
+}
+
+async fn assert_admin_http(req: &Request<AppState>) -> anyhow::Result<()> {
+    if !request_token(req).await?.is_some_and(|ti| ti.admin) {
+        Err(anyhow!("forbidden"))
+    } else {
+        Ok(())
+    }
+}
+
+/// token looked up by the rate limiter, kept in the request extensions.
+#[derive(Clone)]
+struct RequestToken(Option<TokenInfo>);
+
+/// the request's token if known, only looked up when the rate limiter didn't.
+async fn request_token(req: &Request<AppState>) -> anyhow::Result<Option<TokenInfo>> {
+    if let Some(RequestToken(ti)) = req.ext::<RequestToken>() {
+        return Ok(ti.clone());
+    }
 
-    assert_access(
+    lookup_token(
         &*req.state().db.read().await,
         &*req.state().fs.read().await,
-        permtype, token,
-        coll_slug).await
-}
\ No newline at end of file
+        http_token(req)).await
+}
+
+/// permissions of the request's token (the anonymous ones without a known token).
+async fn token_info(req: &Request<AppState>) -> anyhow::Result<TokenInfo> {
+    Ok(match request_token(req).await? {
+        Some(ti) => ti,
+        None => req.state().fs.read().await.get_default_permissions()
+    })
+}
+
+/// wacz archives need to seek, so they are written to a buffer file
+/// which is unlinked right away and read back through a second handle.
+async fn wacz_buffer(fs: &FileSystem, title: &str) -> anyhow::Result<(WaczWriter<BufWriter<std::fs::File>>, tokio::fs::File)> {
+    let (tmp_dir, _) = fs.get_buffer_path(&format!("wacz.{}", Uuid::new_v4()), true).await?;
+    let path = format!("{}export.wacz", tmp_dir);
+    let writer = std::fs::File::create(&path)?;
+    let reader = tokio::fs::File::open(&path).await?;
+    tokio::fs::remove_dir_all(&tmp_dir).await?;
+
+    Ok((WaczWriter::new(BufWriter::new(writer), title)?, reader))
+}
+
+/// streams the archive written by `wacz_buffer`.
+fn wacz_response(wacz: WaczWriter<BufWriter<std::fs::File>>, reader: tokio::fs::File, filename: &str) -> tide::Result {
+    let len = wacz.finish()?.into_inner().map_err(|e| e.into_error())?.metadata()?.len();
+
+    Ok(Response::builder(200)
+        .header("Content-Disposition", format!("attachment; filename=\"{}\"", filename))
+        .body(Body::from_reader(tokio::io::BufReader::new(reader).compat(), Some(len as usize)))
+        .content_type("application/zip")
+        .build())
+}
+
+/// uuids of the collections the request's token can read.
+async fn readable_collection_uuids(req: &Request<AppState>) -> anyhow::Result<HashSet<String>> {
+    accessible_collection_uuids(req, PermissionType::READ).await
+}
+
+/// uuids of the collections the request's token has `permtype` on.
+async fn accessible_collection_uuids(req: &Request<AppState>, permtype: PermissionType) -> anyhow::Result<HashSet<String>> {
+    let token_info = token_info(req).await?;
+    let fs = req.state().fs.read().await;
+
+    let mut uuids = HashSet::new();
+    for slug in fs.get_collection_list().await {
+        if token_info.has_access(permtype, &slug) {
+            uuids.insert(fs.get_coll_uuid(&slug).await?);
+        }
+    }
+
+    Ok(uuids)
+}
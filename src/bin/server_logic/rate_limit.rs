@@ -0,0 +1,228 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use std::{collections::HashMap, io, net::SocketAddr, pin::Pin, sync::{atomic::{AtomicU64, Ordering}, Arc}, task::{ready, Context, Poll}, time::Duration};
+use async_std::io::{BufRead, Read};
+use chrono::{NaiveDate, Utc};
+use log::error;
+use masstuffy::{database::DBManager, permissions::lookup_token};
+use tide::{Body, Middleware, Next, Request, Response};
+use tokio::sync::Mutex;
+
+use super::{http_token, AppState, RequestToken};
+
+/// how often usage counters are written to the database
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct Usage {
+    requests: u64,
+    bytes: u64,
+    throttled: u64
+}
+
+/// fixed window of a client
+struct Window {
+    start: i64,
+    requests: u64,
+    /// shared with the bodies being sent
+    bytes: Arc<AtomicU64>
+}
+
+impl Window {
+    fn new(start: i64) -> Self {
+        Window{start, requests: 0, bytes: Arc::new(AtomicU64::new(0))}
+    }
+}
+
+/// limits requests and bytes served per token (or ip address of anonymous clients)
+/// and counts them in `masstuffy_usage`.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    windows: Arc<Mutex<HashMap<String, Window>>>,
+    /// counters not written to the database yet
+    pending: Arc<std::sync::Mutex<HashMap<(String, NaiveDate), Usage>>>
+}
+
+impl RateLimiter {
+    /// writes usage counters every `FLUSH_INTERVAL`.
+    pub fn spawn_flusher(&self, state: AppState) {
+        let limiter = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(FLUSH_INTERVAL).await;
+                let window_len = state.fs.read().await.get_rate_limit_window();
+                if let Err(e) = limiter.flush(&*state.db.read().await, window_len).await {
+                    error!("unable to save usage counters: {:?}", e);
+                }
+            }
+        });
+    }
+
+    /// writes the pending counters and forgets the windows that ended.
+    async fn flush(&self, db: &DBManager, window_len: i64) -> anyhow::Result<()> {
+        let now = Utc::now().timestamp();
+        self.windows.lock().await.retain(|_, w| now < w.start + window_len);
+
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        let mut pending = pending.into_iter();
+        while let Some(((client, day), usage)) = pending.next() {
+            if let Err(e) = db.add_usage(&client, day,
+                usage.requests as i64, usage.bytes as i64, usage.throttled as i64).await {
+                /* counters are kept for the next flush */
+                let mut current = self.pending.lock().unwrap();
+                for (key, usage) in std::iter::once(((client, day), usage)).chain(pending) {
+                    let entry = current.entry(key).or_default();
+                    entry.requests += usage.requests;
+                    entry.bytes += usage.bytes;
+                    entry.throttled += usage.throttled;
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn count(&self, client: &str, requests: u64, bytes: u64, throttled: u64) {
+        let mut pending = self.pending.lock().unwrap();
+        let usage = pending.entry((client.to_string(), Utc::now().date_naive())).or_default();
+        usage.requests += requests;
+        usage.bytes += bytes;
+        usage.throttled += throttled;
+    }
+}
+
+/// peer's address, forwarding headers can't be trusted.
+fn client_ip(req: &Request<AppState>) -> String {
+    match req.peer_addr() {
+        Some(addr) => addr.parse::<SocketAddr>()
+            .map(|a| a.ip().to_string())
+            .unwrap_or(addr.to_string()),
+        None => "unknown".to_string()
+    }
+}
+
+fn too_many_requests(retry_after: i64) -> Response {
+    Response::builder(429)
+        .header("Retry-After", retry_after.max(1).to_string())
+        .body("too many requests")
+        .build()
+}
+
+/// response body counting the bytes sent against the client's window,
+/// the request is counted once the body is dropped.
+struct CountedBody {
+    body: Body,
+    sent: u64,
+    window_bytes: Arc<AtomicU64>,
+    limiter: RateLimiter,
+    client: String
+}
+
+impl CountedBody {
+    fn sent(&mut self, n: usize) {
+        self.sent += n as u64;
+        self.window_bytes.fetch_add(n as u64, Ordering::Relaxed);
+    }
+}
+
+impl Read for CountedBody {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let n = ready!(Pin::new(&mut this.body).poll_read(cx, buf))?;
+        this.sent(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl BufRead for CountedBody {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut self.get_mut().body).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        Pin::new(&mut this.body).consume(amt);
+        this.sent(amt);
+    }
+}
+
+impl Drop for CountedBody {
+    fn drop(&mut self) {
+        self.limiter.count(&self.client, 1, self.sent, 0);
+    }
+}
+
+#[tide::utils::async_trait]
+impl Middleware<AppState> for RateLimiter {
+    async fn handle(&self, mut req: Request<AppState>, next: Next<'_, AppState>) -> tide::Result {
+        let token = {
+            let fs = req.state().fs.read().await;
+            lookup_token(&*req.state().db.read().await, &fs, http_token(&req)).await?
+        };
+
+        /* signed urls are counted against the client's address rather than the signer:
+           the signature is a capability bound to a record, not to the token which made it,
+           so a shared url can't drain the budget of its signer. */
+        let (client, window_len, (max_requests, max_bytes)) = {
+            let fs = req.state().fs.read().await;
+            match &token {
+                Some(token) => (token.id.clone(), fs.get_rate_limit_window(), fs.get_rate_limits(false)),
+                None => (format!("ip:{}", client_ip(&req)), fs.get_rate_limit_window(), fs.get_rate_limits(true))
+            }
+        };
+        req.set_ext(RequestToken(token));
+
+        /* the budget is checked before running the handler: a response started within it
+           is sent whole (and counted as it is sent), the following requests are refused. */
+        let now = Utc::now().timestamp();
+        let retry_after = {
+            let mut windows = self.windows.lock().await;
+            let window = windows.entry(client.clone())
+                .or_insert_with(|| Window::new(now));
+            if now >= window.start + window_len {
+                *window = Window::new(now);
+            }
+
+            if (max_requests != 0 && window.requests >= max_requests)
+            || (max_bytes != 0 && window.bytes.load(Ordering::Relaxed) >= max_bytes) {
+                Err(window.start + window_len - now)
+            } else {
+                window.requests += 1;
+                Ok(window.bytes.clone())
+            }
+        };
+
+        let window_bytes = match retry_after {
+            Ok(window_bytes) => window_bytes,
+            Err(retry_after) => {
+                self.count(&client, 0, 0, 1);
+                return Ok(too_many_requests(retry_after));
+            }
+        };
+
+        let mut res = next.run(req).await;
+
+        let body = res.take_body();
+        let (len, mime) = (body.len(), body.mime().clone());
+        let mut body = Body::from_reader(CountedBody{body, sent: 0, window_bytes, limiter: self.clone(), client}, len);
+        body.set_mime(mime);
+        res.set_body(body);
+        Ok(res)
+    }
+}
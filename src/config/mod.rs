@@ -27,8 +27,62 @@ pub struct Config {
     pub anonymous_read_perms: String,
     pub anonymous_write_perms_kind: String,
     pub anonymous_write_perms: String,
-    pub anonymous_delete_perms_kind: String,
-    pub anonymous_delete_perms: String,
+    /// `anonymous_delete_perms(_kind)` of older files granted nothing and is ignored
+    #[serde(default = "default_perms_kind_none")]
+    pub anonymous_delete_record_perms_kind: String,
+    #[serde(default)]
+    pub anonymous_delete_record_perms: String,
+    #[serde(default = "default_perms_kind_none")]
+    pub anonymous_create_coll_perms_kind: String,
+    #[serde(default)]
+    pub anonymous_create_coll_perms: String,
+    #[serde(default = "default_perms_kind_none")]
+    pub anonymous_delete_coll_perms_kind: String,
+    #[serde(default)]
+    pub anonymous_delete_coll_perms: String,
+    #[serde(default = "default_perms_kind_any")]
+    pub anonymous_list_coll_perms_kind: String,
+    #[serde(default)]
+    pub anonymous_list_coll_perms: String,
+    /// maximum number of records returned by a search
+    #[serde(default = "default_search_max_limit")]
+    pub search_max_limit: i64,
+    /// maximum lifetime of signed urls (seconds)
+    #[serde(default = "default_signed_url_max_lifetime")]
+    pub signed_url_max_lifetime: i64,
+    /// length of rate limiting windows (seconds)
+    #[serde(default = "default_rate_limit_window")]
+    pub rate_limit_window: i64,
+    /// requests and bytes per window and token (0: unlimited)
+    #[serde(default)]
+    pub token_rate_limit_requests: u64,
+    #[serde(default)]
+    pub token_rate_limit_bytes: u64,
+    /// requests and bytes per window and ip address of anonymous clients (0: unlimited)
+    #[serde(default)]
+    pub anonymous_rate_limit_requests: u64,
+    #[serde(default)]
+    pub anonymous_rate_limit_bytes: u64,
+}
+
+fn default_perms_kind_none() -> String {
+    "none".to_string()
+}
+
+fn default_perms_kind_any() -> String {
+    "any".to_string()
+}
+
+fn default_search_max_limit() -> i64 {
+    1000
+}
+
+fn default_signed_url_max_lifetime() -> i64 {
+    7*24*3600
+}
+
+fn default_rate_limit_window() -> i64 {
+    60
 }
 
 impl Config {
@@ -47,8 +101,21 @@ impl Default for Config {
             anonymous_read_perms: String::new(),
             anonymous_write_perms_kind: "any".to_string(),
             anonymous_write_perms: String::new(),
-            anonymous_delete_perms_kind: "any".to_string(),
-            anonymous_delete_perms: String::new(),
+            anonymous_delete_record_perms_kind: default_perms_kind_none(),
+            anonymous_delete_record_perms: String::new(),
+            anonymous_create_coll_perms_kind: default_perms_kind_none(),
+            anonymous_create_coll_perms: String::new(),
+            anonymous_delete_coll_perms_kind: default_perms_kind_none(),
+            anonymous_delete_coll_perms: String::new(),
+            anonymous_list_coll_perms_kind: default_perms_kind_any(),
+            anonymous_list_coll_perms: String::new(),
+            search_max_limit: default_search_max_limit(),
+            signed_url_max_lifetime: default_signed_url_max_lifetime(),
+            rate_limit_window: default_rate_limit_window(),
+            token_rate_limit_requests: 0,
+            token_rate_limit_bytes: 0,
+            anonymous_rate_limit_requests: 0,
+            anonymous_rate_limit_bytes: 0,
         }
     }
 }
\ No newline at end of file
@@ -16,16 +16,87 @@
  *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
  **/
  
+use std::{fmt::Display, str::FromStr};
+
 use anyhow::Ok;
-use chrono::NaiveDateTime;
-use sqlx::postgres::PgPool;
-use structs::DBWarcRecord;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use data_encoding::BASE64URL_NOPAD;
+use sqlx::{postgres::PgPool, PgExecutor};
+use structs::{DBUsage, DBWarcRecord, RECORD_FLAG_ACTIVE};
 use log::info;
 
-use crate::{constants::MASSTUFFY_DATE_FMT, database::structs::DBToken, permissions::TokenInfo, warc::{cdx::CDXRecord, massaged_url::{massage_url, massaged_url_pattern, Match}}};
+use crate::{constants::MASSTUFFY_DATE_FMT, database::structs::DBToken, permissions::{hash_token, TokenInfo}, warc::{cdx::CDXRecord, massaged_url::{massage_url, massaged_url_pattern, Match}}};
 
 pub mod structs;
 
+/// what `DBManager::search` looks for, `None` matches anything.
+#[derive(Default)]
+pub struct SearchFilters {
+    pub host: Match,
+    pub port: Option<u16>,
+    pub path: Match,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    pub record_type: Option<String>,
+    /// collection's uuid
+    pub collection: Option<String>,
+    /// uuids of the only collections records may come from
+    pub collections: Option<Vec<String>>,
+    pub mime: Option<String>,
+    pub status: Option<i16>
+}
+
+/// position of a record in the order of `DBManager::search` (or `search_surt_ordered`).
+/// made of the sort key since row ids change when a collection is rewritten,
+/// the collection and the position in its files break ties between copies of a record.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchCursor {
+    massaged_url: String,
+    date: NaiveDateTime,
+    identifier: String,
+    collection: String,
+    filename: String,
+    offset: i64
+}
+
+impl SearchCursor {
+    /// the cursor of the page following `record`.
+    pub fn after(record: &DBWarcRecord) -> Self {
+        SearchCursor{
+            massaged_url: record.massaged_url.clone(),
+            date: record.date,
+            identifier: record.identifier.clone(),
+            collection: record.collection.clone(),
+            filename: record.filename.clone(),
+            offset: record.offset
+        }
+    }
+}
+
+impl Display for SearchCursor {
+    /// opaque form used in `after` parameters.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let key = (
+            &self.massaged_url, self.date.and_utc().timestamp_micros(),
+            &self.identifier, &self.collection, &self.filename, self.offset);
+        f.write_str(&BASE64URL_NOPAD.encode(&serde_json::to_vec(&key).map_err(|_| std::fmt::Error)?))
+    }
+}
+
+impl FromStr for SearchCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (massaged_url, date, identifier, collection, filename, offset): (String, i64, String, String, String, i64) =
+            serde_json::from_slice(&BASE64URL_NOPAD.decode(s.as_bytes())?)?;
+        let Some(date) = DateTime::from_timestamp_micros(date) else {
+            anyhow::bail!("invalid cursor date");
+        };
+
+        Ok(SearchCursor{massaged_url, date: date.naive_utc(), identifier, collection, filename, offset})
+    }
+}
+
 pub struct DBManager {
     is_setup: bool,
     db: PgPool
@@ -56,29 +127,43 @@ impl DBManager {
 
     // TODO: insert from iterator
     pub async fn insert_record(&self, coll: &str, record: &CDXRecord, flags: i32, dict_id: Option<i64>, dict_type: Option<&str>) -> anyhow::Result<()> {
-        let massaged_url = massage_url(record.get_url().as_deref().unwrap_or("")).unwrap_or("".to_string());
+        insert_record_with(&self.db, coll, record, flags, dict_id, dict_type).await
+    }
 
-        sqlx::query!(r#"
-        INSERT INTO masstuffy_records(
-            flags, date, identifier,
-            collection, filename, "offset", "type",
-            uri, dict_id, dict_type, massaged_url,
-            raw_size)
-        VALUES(
-            $1, to_timestamp($2, 'YYYYMMDDHH24MISS'), $3,
-            $4, $5, $6, $7, $8, $9, $10, $11,
-            $12)"#,
-        flags, record.get_date(), record.get_record_id(),
-        coll, record.get_file_name().unwrap(), record.get_file_offset().unwrap(),
-        record.get_record_type(), record.get_url(), dict_id, dict_type,
-        massaged_url, record.get_raw_size().unwrap() as i32)
-            .execute(&self.db).await?;
+    /// replaces every record of a collection in a single transaction,
+    /// so readers never see a partially swapped collection.
+    /// `deleted` records are kept as inactive rows.
+    pub async fn replace_collection_records(&self, coll: &str, records: &[CDXRecord], deleted: &[CDXRecord], dict_id: Option<i64>, dict_type: Option<&str>) -> anyhow::Result<()> {
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query!(
+        r#"
+        DELETE FROM masstuffy_records
+        WHERE collection = $1
+        "#, coll).execute(&mut *tx).await?;
+
+        for record in records {
+            insert_record_with(&mut *tx, coll, record, RECORD_FLAG_ACTIVE, dict_id, dict_type).await?;
+        }
+
+        for record in deleted {
+            insert_record_with(&mut *tx, coll, record, 0, dict_id, dict_type).await?;
+        }
+
+        tx.commit().await?;
         Ok(())
     }
 
-    pub async fn get_record_from_id(&self, id: String) -> anyhow::Result<DBWarcRecord> {
+    /// identifiers are not unique across collections, `collections` (uuids) restricts the lookup.
+    pub async fn get_record_from_id(&self, id: String, collections: Option<&[String]>) -> anyhow::Result<DBWarcRecord> {
         let record: DBWarcRecord = sqlx::query_as!(DBWarcRecord,
-            "SELECT * FROM masstuffy_records WHERE identifier=$1 AND (flags&1) = 1 LIMIT 1", id).fetch_one(&self.db).await?.into();
+            r#"SELECT * FROM masstuffy_records
+            WHERE
+                identifier=$1 AND
+                (flags&1) = 1 AND
+                ($2::text[] IS NULL OR collection = ANY($2))
+            ORDER BY id
+            LIMIT 1"#, id, collections as Option<&[String]>).fetch_one(&self.db).await?.into();
         Ok(record)
     }
 
@@ -88,39 +173,111 @@ impl DBManager {
         SET flags = flags|1
         WHERE
             collection = $1 AND
-            dict_id    = $2 AND
-            dict_type  = $3"#,
+            dict_id    IS NOT DISTINCT FROM $2 AND
+            dict_type  IS NOT DISTINCT FROM $3"#,
         collection, dict_id, dict_type)
             .execute(&self.db).await?;
         Ok(())
     }
 
+    pub async fn deactivate_record(&self, collection: &str, id: &str) -> anyhow::Result<u64> {
+        Ok(sqlx::query!(r#"
+        UPDATE masstuffy_records
+        SET flags = flags & ~1
+        WHERE
+            collection = $1 AND
+            identifier = $2 AND
+            (flags&1) = 1"#,
+        collection, id)
+            .execute(&self.db).await?.rows_affected())
+    }
+
+    /// identifiers deactivated in the current storage of a collection.
+    pub async fn get_inactive_identifiers(&self, collection: &str, dict_id: Option<i64>, dict_type: Option<&str>) -> anyhow::Result<Vec<String>> {
+        Ok(sqlx::query_scalar!(r#"
+        SELECT identifier FROM masstuffy_records
+        WHERE
+            collection = $1 AND
+            dict_id IS NOT DISTINCT FROM $2 AND
+            dict_type IS NOT DISTINCT FROM $3 AND
+            (flags&1) = 0"#,
+        collection, dict_id, dict_type)
+            .fetch_all(&self.db).await?)
+    }
+
     pub async fn delete_records(&self, collection: &String, dict_id: Option<i64>, dict_type: Option<&str>) -> anyhow::Result<()> {
         sqlx::query!(r#"
         DELETE FROM masstuffy_records
         WHERE
             collection = $1 AND
-            dict_id    = $2 AND
-            dict_type  = $3"#,
+            dict_id    IS NOT DISTINCT FROM $2 AND
+            dict_type  IS NOT DISTINCT FROM $3"#,
             collection, dict_id, dict_type)
             .execute(&self.db).await?;
         Ok(())
     }
 
-    pub async fn get_record_from_uri(&self, date: &String, uri: &String) -> anyhow::Result<DBWarcRecord> {
+    /// `collections` (uuids) restricts the lookup.
+    pub async fn get_record_from_uri(&self, date: &String, uri: &String, collections: Option<&[String]>) -> anyhow::Result<DBWarcRecord> {
         // TOOD: better way than comparing epoches?
         let record: DBWarcRecord = sqlx::query_as!(DBWarcRecord,
             r#"SELECT * FROM masstuffy_records
             WHERE
                 "type" != 'request' AND
                 uri=$1 AND
-                (flags&1) = 1
-            ORDER BY ABS(DATE_PART('epoch', date) - DATE_PART('epoch', $2::timestamp)) ASC
+                (flags&1) = 1 AND
+                ($3::text[] IS NULL OR collection = ANY($3))
+            ORDER BY ABS(DATE_PART('epoch', date) - DATE_PART('epoch', $2::timestamp)) ASC, id
             LIMIT 1
-            "#, uri, NaiveDateTime::parse_from_str(date, MASSTUFFY_DATE_FMT)?).fetch_one(&self.db).await?.into();
+            "#, uri, NaiveDateTime::parse_from_str(date, MASSTUFFY_DATE_FMT)?, collections as Option<&[String]>).fetch_one(&self.db).await?.into();
         Ok(record)
     }
 
+    /// the `limit` oldest active captures of `uri` in `collections`.
+    pub async fn get_captures(&self, uri: &str, collections: &[String], limit: i64) -> anyhow::Result<Vec<DBWarcRecord>> {
+        Ok(sqlx::query_as!(DBWarcRecord,
+            r#"SELECT * FROM masstuffy_records
+            WHERE
+                "type" != 'request' AND
+                uri=$1 AND
+                (flags&1) = 1 AND
+                collection = ANY($2)
+            ORDER BY date ASC
+            LIMIT $3
+            "#, uri, collections, limit).fetch_all(&self.db).await?)
+    }
+
+    /// the active capture of `uri` in `collections` closest to `date` (the latest one without it).
+    pub async fn get_closest_capture(&self, uri: &str, date: Option<NaiveDateTime>, collections: &[String]) -> anyhow::Result<Option<DBWarcRecord>> {
+        Ok(sqlx::query_as!(DBWarcRecord,
+            r#"SELECT * FROM masstuffy_records
+            WHERE
+                "type" != 'request' AND
+                uri=$1 AND
+                (flags&1) = 1 AND
+                collection = ANY($3)
+            ORDER BY
+                CASE WHEN $2::timestamp IS NULL THEN -DATE_PART('epoch', date)
+                ELSE ABS(DATE_PART('epoch', date) - DATE_PART('epoch', $2::timestamp)) END,
+                date, id
+            LIMIT 1
+            "#, uri, date, collections).fetch_optional(&self.db).await?)
+    }
+
+    /// the oldest active response of `collection` whose payload has `digest`.
+    pub async fn get_payload_original(&self, collection: &str, digest: &str) -> anyhow::Result<Option<DBWarcRecord>> {
+        Ok(sqlx::query_as!(DBWarcRecord,
+            r#"SELECT * FROM masstuffy_records
+            WHERE
+                collection=$1 AND
+                payload_digest=$2 AND
+                "type" = 'response' AND
+                (flags&1) = 1
+            ORDER BY date ASC
+            LIMIT 1
+            "#, collection, digest).fetch_optional(&self.db).await?)
+    }
+
     pub async fn get_samples(&self, collection: &str, limit: i64) -> anyhow::Result<Vec<DBWarcRecord>> {
         Ok(sqlx::query_as!(
             DBWarcRecord,
@@ -133,22 +290,108 @@ impl DBManager {
             fetch_all(&self.db).await?) // TODO: make it random?
     }
 
-    pub async fn search(&self, 
-        host: Match,
-        port: Option<u16>,
-        path: Match,
-        limit: i64) -> anyhow::Result<Vec<DBWarcRecord>> {
-        let pattern = massaged_url_pattern(host, port, path);
+    /// active records matching `filters` by capture date, starting after `after`.
+    pub async fn search(&self, filters: SearchFilters, after: Option<&SearchCursor>, limit: i64) -> anyhow::Result<Vec<DBWarcRecord>> {
+        let pattern = massaged_url_pattern(filters.host, filters.port, filters.path);
+
+        Ok(sqlx::query_as!(
+            DBWarcRecord,
+            r#"
+            SELECT * FROM masstuffy_records
+            WHERE massaged_url ~ $1
+            AND (flags&1) = 1
+            AND ($2::timestamp IS NULL OR (date, identifier, collection, filename, "offset") > ($2, $3, $4, $5, $6))
+            AND ($7::timestamp IS NULL OR date >= $7)
+            AND ($8::timestamp IS NULL OR date <= $8)
+            AND ($9::text IS NULL OR "type" = $9)
+            AND ($10::text IS NULL OR collection = $10)
+            AND ($11::text IS NULL OR mime = $11)
+            AND ($12::smallint IS NULL OR status = $12)
+            AND ($13::text[] IS NULL OR collection = ANY($13))
+            ORDER BY date, identifier, collection, filename, "offset"
+            LIMIT $14"#,
+            pattern,
+            after.map(|c| c.date),
+            after.map(|c| c.identifier.as_str()),
+            after.map(|c| c.collection.as_str()),
+            after.map(|c| c.filename.as_str()),
+            after.map(|c| c.offset),
+            filters.from, filters.to,
+            filters.record_type, filters.collection,
+            filters.mime, filters.status,
+            filters.collections.as_deref(),
+            limit).
+            fetch_all(&self.db).await?)
+    }
+
+    /// same as `search` but in the order of CDX indexes (SURT key, then date).
+    pub async fn search_surt_ordered(&self, filters: SearchFilters, after: Option<&SearchCursor>, limit: i64) -> anyhow::Result<Vec<DBWarcRecord>> {
+        let pattern = massaged_url_pattern(filters.host, filters.port, filters.path);
 
         Ok(sqlx::query_as!(
             DBWarcRecord,
             r#"
             SELECT * FROM masstuffy_records
             WHERE massaged_url ~ $1
-            LIMIT $2"#, pattern, limit).
+            AND (flags&1) = 1
+            AND ($2::text IS NULL OR (masstuffy_surt(massaged_url) COLLATE "C", date, identifier, collection, filename, "offset")
+                > (masstuffy_surt($2) COLLATE "C", $3, $4, $5, $6, $7))
+            AND ($8::timestamp IS NULL OR date >= $8)
+            AND ($9::timestamp IS NULL OR date <= $9)
+            AND ($10::text IS NULL OR "type" = $10)
+            AND ($11::text IS NULL OR collection = $11)
+            AND ($12::text IS NULL OR mime = $12)
+            AND ($13::smallint IS NULL OR status = $13)
+            AND ($14::text[] IS NULL OR collection = ANY($14))
+            ORDER BY masstuffy_surt(massaged_url) COLLATE "C", date, identifier, collection, filename, "offset"
+            LIMIT $15"#,
+            pattern,
+            after.map(|c| c.massaged_url.as_str()),
+            after.map(|c| c.date),
+            after.map(|c| c.identifier.as_str()),
+            after.map(|c| c.collection.as_str()),
+            after.map(|c| c.filename.as_str()),
+            after.map(|c| c.offset),
+            filters.from, filters.to,
+            filters.record_type, filters.collection,
+            filters.mime, filters.status,
+            filters.collections.as_deref(),
+            limit).
+            fetch_all(&self.db).await?)
+    }
+
+    pub async fn get_collection_records(&self, collection: &str, after_id: i64, limit: i64) -> anyhow::Result<Vec<DBWarcRecord>> {
+        Ok(sqlx::query_as!(
+            DBWarcRecord,
+            r#"
+            SELECT * FROM masstuffy_records
+            WHERE collection=$1
+            AND id > $2
+            ORDER BY id
+            LIMIT $3"#, collection, after_id, limit).
+            fetch_all(&self.db).await?)
+    }
+
+    pub async fn get_collection_uuids(&self) -> anyhow::Result<Vec<String>> {
+        Ok(sqlx::query_scalar!(
+            "SELECT DISTINCT collection FROM masstuffy_records").
             fetch_all(&self.db).await?)
     }
 
+    pub async fn set_record_flags(&self, id: i64, flags: i32) -> anyhow::Result<()> {
+        sqlx::query!(
+            "UPDATE masstuffy_records SET flags = $2 WHERE id = $1",
+            id, flags).execute(&self.db).await?;
+        Ok(())
+    }
+
+    pub async fn delete_record_row(&self, id: i64) -> anyhow::Result<()> {
+        sqlx::query!(
+            "DELETE FROM masstuffy_records WHERE id = $1",
+            id).execute(&self.db).await?;
+        Ok(())
+    }
+
     pub async fn delete_collection(&self, collection: &String) -> anyhow::Result<()> {
         sqlx::query!(
         r#"
@@ -158,24 +401,66 @@ impl DBManager {
         Ok(())
     }
 
-    pub async fn get_permissions(&self, token: &str) -> anyhow::Result<Option<TokenInfo>> {
+    /// unexpired token holding the given hash (see `permissions::hash_token`).
+    pub async fn get_permissions(&self, token_hash: &str) -> anyhow::Result<Option<TokenInfo>> {
         Ok(sqlx::query_as!(
             DBToken,
             r#"
             SELECT * FROM masstuffy_tokens
-            WHERE token = $1
-            LIMIT 1"#, token).
-            fetch_optional(&self.db).await?.map(|t| TokenInfo::from_db_row(t)))
+            WHERE token = $1 AND hashed
+            AND (expires_at IS NULL OR expires_at > NOW())
+            LIMIT 1"#, token_hash).
+            fetch_optional(&self.db).await?.map(TokenInfo::from_db_row))
     }
 
-    pub async fn delete_permissions(&self, token: &String) -> anyhow::Result<()> {
-        sqlx::query_as!(
+    pub async fn get_permissions_by_id(&self, id: &str) -> anyhow::Result<Option<TokenInfo>> {
+        Ok(sqlx::query_as!(
             DBToken,
-            r#"DELETE FROM masstuffy_tokens WHERE token = $1"#, token).
-            execute(&self.db).await?;
+            r#"
+            SELECT * FROM masstuffy_tokens
+            WHERE id = $1
+            LIMIT 1"#, id).
+            fetch_optional(&self.db).await?.map(TokenInfo::from_db_row))
+    }
+
+    /// records the use of a token (at most once a minute).
+    pub async fn touch_token(&self, id: &str) -> anyhow::Result<()> {
+        sqlx::query!(r#"
+        UPDATE masstuffy_tokens
+        SET last_used_at = NOW()
+        WHERE id = $1
+        AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')"#, id)
+            .execute(&self.db).await?;
         Ok(())
     }
 
+    /// replaces the hash of a token, returns how many tokens were updated.
+    pub async fn set_token_hash(&self, id: &str, token_hash: &str) -> anyhow::Result<u64> {
+        Ok(sqlx::query!(r#"
+        UPDATE masstuffy_tokens
+        SET token = $2, hashed = true
+        WHERE id = $1"#, id, token_hash)
+            .execute(&self.db).await?.rows_affected())
+    }
+
+    /// hashes the tokens stored before they were, returns how many were hashed.
+    pub async fn hash_plain_tokens(&self, secret_key: &str) -> anyhow::Result<usize> {
+        let plain = sqlx::query!(
+            "SELECT id, token FROM masstuffy_tokens WHERE NOT hashed")
+            .fetch_all(&self.db).await?;
+
+        for row in &plain {
+            self.set_token_hash(&row.id, &hash_token(secret_key, &row.token)).await?;
+        }
+        Ok(plain.len())
+    }
+
+    pub async fn delete_permissions(&self, id: &str) -> anyhow::Result<u64> {
+        Ok(sqlx::query!(
+            r#"DELETE FROM masstuffy_tokens WHERE id = $1"#, id).
+            execute(&self.db).await?.rows_affected())
+    }
+
     pub async fn get_all_permissions(&self) -> anyhow::Result<Vec<TokenInfo>> {
         Ok(sqlx::query_as!(
             DBToken,
@@ -187,20 +472,127 @@ impl DBManager {
     pub async fn create_permissions(&self, perms: TokenInfo) -> anyhow::Result<()> {
         sqlx::query!(r#"
         INSERT INTO masstuffy_tokens(
-                token, comment,
+                id, token, hashed, comment,
                 read_perms_kind, read_perms,
                 write_perms_kind, write_perms,
-                delete_perms_kind, delete_perms)
+                delete_perms_kind, delete_perms,
+                admin,
+                create_coll_perms_kind, create_coll_perms,
+                delete_coll_perms_kind, delete_coll_perms,
+                list_coll_perms_kind, list_coll_perms,
+                expires_at)
         VALUES(
-            $1, $2,
-            $3, $4,
-            $5, $6,
-            $7, $8)"#,
-            perms.token, perms.comment,
+            $1, $2, true, $3,
+            $4, $5,
+            $6, $7,
+            $8, $9,
+            $10,
+            $11, $12,
+            $13, $14,
+            $15, $16,
+            $17)"#,
+            perms.id, perms.token_hash, perms.comment,
+            perms.read_perms.get_perms_kind(), perms.read_perms.get_perms(),
+            perms.write_perms.get_perms_kind(), perms.write_perms.get_perms(),
+            perms.delete_perms.get_perms_kind(), perms.delete_perms.get_perms(),
+            perms.admin,
+            perms.create_coll_perms.get_perms_kind(), perms.create_coll_perms.get_perms(),
+            perms.delete_coll_perms.get_perms_kind(), perms.delete_coll_perms.get_perms(),
+            perms.list_coll_perms.get_perms_kind(), perms.list_coll_perms.get_perms(),
+            perms.expires_at)
+            .execute(&self.db).await?;
+        Ok(())
+    }
+
+    /// replaces the comment, permissions and expiry of a token, returns how many tokens were updated.
+    pub async fn update_permissions(&self, perms: &TokenInfo) -> anyhow::Result<u64> {
+        Ok(sqlx::query!(r#"
+        UPDATE masstuffy_tokens
+        SET
+            comment = $2,
+            read_perms_kind = $3, read_perms = $4,
+            write_perms_kind = $5, write_perms = $6,
+            delete_perms_kind = $7, delete_perms = $8,
+            admin = $9,
+            create_coll_perms_kind = $10, create_coll_perms = $11,
+            delete_coll_perms_kind = $12, delete_coll_perms = $13,
+            list_coll_perms_kind = $14, list_coll_perms = $15,
+            expires_at = $16
+        WHERE id = $1"#,
+            perms.id, perms.comment,
             perms.read_perms.get_perms_kind(), perms.read_perms.get_perms(),
             perms.write_perms.get_perms_kind(), perms.write_perms.get_perms(),
-            perms.delete_perms.get_perms_kind(), perms.delete_perms.get_perms())
+            perms.delete_perms.get_perms_kind(), perms.delete_perms.get_perms(),
+            perms.admin,
+            perms.create_coll_perms.get_perms_kind(), perms.create_coll_perms.get_perms(),
+            perms.delete_coll_perms.get_perms_kind(), perms.delete_coll_perms.get_perms(),
+            perms.list_coll_perms.get_perms_kind(), perms.list_coll_perms.get_perms(),
+            perms.expires_at)
+            .execute(&self.db).await?.rows_affected())
+    }
+
+    /// adds to the usage counters of a client.
+    pub async fn add_usage(&self, client: &str, day: NaiveDate, requests: i64, bytes: i64, throttled: i64) -> anyhow::Result<()> {
+        sqlx::query!(r#"
+        INSERT INTO masstuffy_usage(client, day, requests, bytes, throttled)
+        VALUES($1, $2, $3, $4, $5)
+        ON CONFLICT (client, day) DO UPDATE SET
+            requests = masstuffy_usage.requests + EXCLUDED.requests,
+            bytes = masstuffy_usage.bytes + EXCLUDED.bytes,
+            throttled = masstuffy_usage.throttled + EXCLUDED.throttled"#,
+            client, day, requests, bytes, throttled)
             .execute(&self.db).await?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    pub async fn get_usage(&self, client: Option<&str>, since: Option<NaiveDate>) -> anyhow::Result<Vec<DBUsage>> {
+        Ok(sqlx::query_as!(
+            DBUsage,
+            r#"
+            SELECT * FROM masstuffy_usage
+            WHERE ($1::text IS NULL OR client = $1)
+            AND ($2::date IS NULL OR day >= $2)
+            ORDER BY day, client"#, client, since)
+            .fetch_all(&self.db).await?)
+    }
+}
+
+async fn insert_record_with<'e, E: PgExecutor<'e>>(executor: E, coll: &str, record: &CDXRecord, flags: i32, dict_id: Option<i64>, dict_type: Option<&str>) -> anyhow::Result<()> {
+    let massaged_url = massage_url(record.get_url().as_deref().unwrap_or("")).unwrap_or("".to_string());
+
+    sqlx::query!(r#"
+    INSERT INTO masstuffy_records(
+        flags, date, identifier,
+        collection, filename, "offset", "type",
+        uri, dict_id, dict_type, massaged_url,
+        raw_size, status, mime, payload_length, payload_digest)
+    VALUES(
+        $1, to_timestamp($2, 'YYYYMMDDHH24MISS'), $3,
+        $4, $5, $6, $7, $8, $9, $10, $11,
+        $12, $13, $14, $15, $16)"#,
+    flags, record.get_date(), record.get_record_id(),
+    coll, record.get_file_name().unwrap(), record.get_file_offset().unwrap(),
+    record.get_record_type(), record.get_url(), dict_id, dict_type,
+    massaged_url, record.get_raw_size().unwrap() as i32,
+    record.get_status().map(|s| s as i16), record.get_mime(),
+    record.get_payload_length().map(|l| l as i64), record.get_payload_digest())
+        .execute(executor).await?;
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_cursor_round_trips() {
+        let cursor = SearchCursor{
+            massaged_url: "com,example)/a?b=c d".to_string(),
+            date: NaiveDateTime::parse_from_str("2023-01-02 03:04:05.123456", "%Y-%m-%d %H:%M:%S%.f").unwrap(),
+            identifier: "urn:uuid:1".to_string(),
+            collection: "c1".to_string(),
+            filename: "records.1.warc".to_string(),
+            offset: 42};
+        assert_eq!(cursor.to_string().parse::<SearchCursor>().unwrap(), cursor);
+        assert!("garbage".parse::<SearchCursor>().is_err());
+    }
+}
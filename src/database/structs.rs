@@ -16,7 +16,7 @@
  *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
  **/
 
-use chrono::{NaiveDateTime};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 
 #[derive(sqlx::FromRow)]
 pub struct DBWarcRecord {
@@ -32,7 +32,11 @@ pub struct DBWarcRecord {
     pub dict_type: Option<String>,
     pub dict_id: Option<i64>,
     pub massaged_url: String,
-    pub raw_size: i64
+    pub raw_size: i64,
+    pub status: Option<i16>,
+    pub mime: Option<String>,
+    pub payload_length: Option<i64>,
+    pub payload_digest: Option<String>
 }
 
 pub const RECORD_FLAG_ACTIVE: i32 = 1<<0;
@@ -47,5 +51,28 @@ pub struct DBToken {
     pub write_perms_kind: i16,
     pub write_perms: String,
     pub delete_perms_kind: i16,
-    pub delete_perms: String
+    pub delete_perms: String,
+
+    pub admin: bool,
+
+    pub create_coll_perms_kind: i16,
+    pub create_coll_perms: String,
+    pub delete_coll_perms_kind: i16,
+    pub delete_coll_perms: String,
+    pub list_coll_perms_kind: i16,
+    pub list_coll_perms: String,
+
+    pub hashed: bool,
+    pub id: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>
+}
+
+#[derive(sqlx::FromRow)]
+pub struct DBUsage {
+    pub client: String,
+    pub day: NaiveDate,
+    pub requests: i64,
+    pub bytes: i64,
+    pub throttled: i64
 }
\ No newline at end of file
@@ -16,39 +16,57 @@
  *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
 **/
 
-use tokio::{fs, io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader}, sync::RwLock};
+use tokio::{fs, io::{AsyncBufRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader}, sync::{Mutex, RwLock}};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use std::{fmt::Write, io::SeekFrom, sync::Arc};
-use async_compression::{tokio::bufread::{ZstdDecoder, ZstdEncoder}};
+use std::{collections::{HashMap, HashSet}, fmt::Write, io::SeekFrom, sync::{atomic::{AtomicBool, Ordering}, Arc}};
+use async_compression::{tokio::bufread::{GzipDecoder, GzipEncoder, ZstdDecoder, ZstdEncoder}};
 
-use crate::{database::DBManager, utils::seek::FileManager, warc::{cdx::{CDXFileReader, CDXRecord}, read_record, WarcRecord}};
+use crate::{database::{structs::RECORD_FLAG_ACTIVE, DBManager}, utils::{counting::CountingReader, seek::FileManager}, warc::{cdx::{CDXFileReader, CDXRecord, CDX_HEADER}, http::{is_http_response, HttpResponse}, payload::payload_digest, read_record, revisit::{is_identical_payload_revisit, make_revisit, resolve_revisit}, scanner::{RecordCompression, ScannedRecord, WarcFileScanner}, wacz::{WaczReader, WaczWriter}, WarcReader, WarcRecord}};
 
-use super::dict_store::DictStore;
+use super::{dict_store::DictStore, journal::{Journal, JournalOp}, verify::IssueKind};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-struct CollectionManifest {
+pub(super) struct CollectionManifest {
     uuid: String,
     slug: String,
     compression: Option<String>,
     compression_level: i32,
     dict_id: Option<u32>,
-    split_threshold: u64
+    split_threshold: u64,
+    #[serde(default = "default_index_segment_threshold")]
+    index_segment_threshold: u64, // 0 never seals index.cdx
+    #[serde(default = "default_index_compression")]
+    index_compression: String,
+    /// http responses whose payload is already stored become `revisit` records
+    #[serde(default)]
+    dedup: bool
+}
+
+fn default_index_segment_threshold() -> u64 {
+    64 << 20
+}
+
+fn default_index_compression() -> String {
+    "gz".to_string()
 }
 
 impl CollectionManifest {
     pub async fn validate(&self) -> anyhow::Result<()> {
-        if let Some(comp) = &self.compression {
-            if comp != "zstd" {
-                anyhow::bail!("{}: compression '{}' is not supported", self.slug, comp);
-            }
-    
-            if let None = self.dict_id {
-                anyhow::bail!("{}: compression with no dictionary is not supported", self.slug);
-            }
+        match self.compression.as_deref() {
+            Some("zstd") if self.dict_id.is_none() =>
+                anyhow::bail!("{}: zstd compression with no dictionary is not supported", self.slug),
+            Some("gzip") if self.dict_id.is_some() =>
+                anyhow::bail!("{}: gzip compression does not use dictionaries", self.slug),
+            None | Some("zstd") | Some("gzip") => {},
+            Some(comp) => anyhow::bail!("{}: compression '{}' is not supported", self.slug, comp)
+        }
+
+        if self.index_compression != "gz" && self.index_compression != "zst" {
+            anyhow::bail!("{}: index compression '{}' is not supported", self.slug, self.index_compression);
         }
 
         Ok(())
@@ -66,6 +84,9 @@ pub struct Collection {
     dict_store: Arc<DictStore>,
     dict: RwLock<Option<Arc<Vec<u8>>>>,
     fm: FileManager,
+    journal: Journal,
+    write_lock: Mutex<()>, // serialises appends so they can be rolled back
+    read_only: AtomicBool, // set when an append could not be rolled back
     cur_record_file: RwLock<u32> // cache to not reuse stat for every single insert
 }
 
@@ -85,33 +106,54 @@ impl Collection {
             if let Some(id) = &manifest.dict_id {
                 format!(".{}", id)
             } else {"".to_string()},
-            if let Some(alg) = &manifest.compression {
-                format!(".{}", alg)
-            } else {"".to_string()})
+            match manifest.compression.as_deref() {
+                Some("gzip") => ".gz".to_string(), // so other tools recognise it
+                Some(alg) => format!(".{}", alg),
+                None => "".to_string()
+            })
     }
 
-    // TODO: extract http response status code (when available)
     pub async fn add_warc(&self, record: &WarcRecord) -> anyhow::Result<CDXRecord>{
         let manifest = self.manifest.read().await;
         info!("writing new record to `{}`: {}", manifest.slug, record.get_record_id()?);
 
+        self.add_serialized_warc(record.serialize(), CDXRecord::from_warc(record)?).await
+    }
+
+    /// stores a gzip member holding `record`, byte-for-byte if
+    /// the collection is gzip-compressed, transcoded otherwise.
+    pub async fn add_gzip_member(&self, member: Vec<u8>, record: &WarcRecord) -> anyhow::Result<CDXRecord> {
+        let cdx = CDXRecord::from_warc(record)?;
+
+        if self.manifest.read().await.compression.as_deref() == Some("gzip") {
+            let mut cdx_vec: Vec<CDXRecord> = vec![cdx];
+            cdx_vec[0].set_file("-".to_string(), None, Some(member.len() as u64));
+            self.add_raw_warcs(&member, &mut cdx_vec).await?;
+            return Ok(cdx_vec.remove(0));
+        }
+
+        let mut serialized = Vec::new();
+        GzipDecoder::new(&member[..]).read_to_end(&mut serialized).await?;
+        self.add_serialized_warc(serialized, cdx).await
+    }
+
+    /// compresses an uncompressed record as the collection requires then stores it.
+    async fn add_serialized_warc(&self, serialized: Vec<u8>, mut cdx: CDXRecord) -> anyhow::Result<CDXRecord> {
         debug!("compressing record...");
-        let serialized_record = self.compress(record.serialize()).await;
-        let mut cdx = CDXRecord::from_warc(record)?;
-        cdx.set_file("-".to_string(), None, Some(serialized_record.len() as u64));
+        let stored = self.compress(serialized).await;
+        cdx.set_file("-".to_string(), None, Some(stored.len() as u64));
 
         let mut cdx_vec: Vec<CDXRecord> = vec![cdx];
-        self.add_raw_warcs(&serialized_record, &mut cdx_vec).await?;
+        self.add_raw_warcs(&stored, &mut cdx_vec).await?;
         Ok(cdx_vec.remove(0))
     }
 
-    // TODO: flush .cdx to .cdx.gz when enough big \
-    //       don't forget to patch list_records()  \
-    //       and add the CDX header if it is the first flush
-    pub async fn add_raw_warcs(&self, raw_records: &Vec<u8>, cdx_records: &mut Vec<CDXRecord>)  -> anyhow::Result<()> {
+    pub async fn add_raw_warcs(&self, raw_records: &Vec<u8>, cdx_records: &mut [CDXRecord])  -> anyhow::Result<()> {
         let manifest = self.manifest.read().await.clone();
         info!("writing {} new record(s) to {}", cdx_records.len(), manifest.slug);
 
+        let _guard = self.write_lock.lock().await;
+
         debug!("finding available slot...");
         let mut warc_target = String::new();
         let cached_warc_file_id = *self.cur_record_file.read().await;
@@ -132,24 +174,29 @@ impl Collection {
             *self.cur_record_file.write().await = warc_file_id;
         }
 
-        debug!("writing...");
-        let file_offset = self.fm.append(
-            &format!("{}/{}", self.path, warc_target),
-            &raw_records).await?;
+        let warc_path = format!("{}/{}", self.path, warc_target);
+        let index_path = format!("{}/index.cdx", self.path);
+        self.journaled_append(Some(&warc_target), async {
+            debug!("writing...");
+            let file_offset = self.fm.append(&warc_path, &raw_records).await?;
+
+            debug!("updating cdx...");
+            let mut warc_offset = 0;
+            let mut cdx_records_str = String::new();
+            for cdx_rec in cdx_records.iter_mut() {
+                cdx_rec.set_file(warc_target.clone(), Some(file_offset+warc_offset), cdx_rec.get_raw_size());
+                warc_offset += cdx_rec.get_raw_size().unwrap();
+                cdx_records_str.write_fmt(format_args!("{}\n", cdx_rec))?;
+            }
 
-        debug!("updating cdx...");
-        let mut warc_offset = 0;
-        let mut cdx_records_str = String::new();
-        for cdx_rec in cdx_records {
-            cdx_rec.set_file(warc_target.clone(), Some(file_offset+warc_offset), cdx_rec.get_raw_size());
-            warc_offset += cdx_rec.get_raw_size().unwrap();
-            cdx_records_str.write_fmt(format_args!("{}\n", cdx_rec))?;
-        }
+            debug!("writing cdx...");
+            self.fm.append(&index_path, cdx_records_str.as_bytes()).await?;
 
-        debug!("writing cdx...");
-        self.fm.append(&format!("{}/index.cdx", self.path), cdx_records_str.as_bytes()).await?;
+            self.fm.sync(&warc_path).await?;
+            self.fm.sync(&index_path).await
+        }).await?;
 
-        Ok(())
+        self.seal_index_if_needed().await
     }
 
     pub async fn add_raw_warc(&self, raw_record: Vec<u8>, cdx: CDXRecord) -> anyhow::Result<CDXRecord> {
@@ -161,6 +208,268 @@ impl Collection {
         Ok(cdx_vec.remove(0))
     }
 
+    /// appends a record and inserts it in the database (if any), http responses
+    /// whose payload is already stored are replaced by a `revisit` if the collection deduplicates.
+    pub async fn ingest_warc(&self, record: &WarcRecord, db: Option<&DBManager>) -> anyhow::Result<CDXRecord> {
+        let cdx = match self.dedup_revisit(record, db).await? {
+            Some(revisit) => self.add_warc(&revisit).await?,
+            None => self.add_warc(record).await?
+        };
+        self.insert_in_db(&cdx, db).await?;
+        Ok(cdx)
+    }
+
+    /// the revisit to store instead of `record` when the collection
+    /// deduplicates and already holds a response with the same payload.
+    async fn dedup_revisit(&self, record: &WarcRecord, db: Option<&DBManager>) -> anyhow::Result<Option<WarcRecord>> {
+        let Some(db) = db else {
+            return Ok(None);
+        };
+        if !self.manifest.read().await.dedup || !is_http_response(record) {
+            return Ok(None);
+        }
+
+        let Ok((_, payload_start)) = HttpResponse::parse_head(record.get_body()) else {
+            return Ok(None);
+        };
+        let payload = &record.get_body()[payload_start..];
+        if payload.is_empty() {
+            return Ok(None);
+        }
+
+        let digest = payload_digest(payload);
+        let Some(row) = db.get_payload_original(&self.get_uuid().await, &digest).await? else {
+            return Ok(None);
+        };
+        let Some(original) = self.get_record(&row.filename, row.offset).await? else {
+            return Ok(None);
+        };
+
+        /* stored digests may come from crawlers, only trust the actual bytes */
+        match HttpResponse::parse_head(original.get_body()) {
+            Ok((_, start)) if original.get_body()[start..] == *payload => {},
+            _ => return Ok(None)
+        }
+
+        debug!("{} has the same payload as {}", record.get_record_id()?, row.identifier);
+        Ok(Some(make_revisit(record, &original, &digest)?))
+    }
+
+    async fn insert_in_db(&self, cdx: &CDXRecord, db: Option<&DBManager>) -> anyhow::Result<()> {
+        if let Some(db) = db {
+            let (dict_id, dict_algo) = self.get_dict().await;
+            db.insert_record(
+                &self.get_uuid().await, cdx,
+                RECORD_FLAG_ACTIVE,
+                dict_id.map(|id| id as i64), dict_algo.as_deref()).await?; // TODO: bulk insert
+        }
+        Ok(())
+    }
+
+    /// appends every record of a warc file (plain, gzipped or holding a gzip member per record),
+    /// returns how many were added.
+    pub async fn import_warc_file(&self, path: &str, db: Option<&DBManager>) -> anyhow::Result<usize> {
+        let mut count = 0;
+
+        /* gzip members are kept (or transcoded) without recompressing the whole file */
+        if path.ends_with(".gz") && WarcFileScanner::probe(path, RecordCompression::Gzip).await {
+            debug!("{} holds a gzip member per record", path);
+            let mut scanner = WarcFileScanner::open(path, RecordCompression::Gzip).await?
+                .keep_raw(path).await?;
+            while let Some(scanned) = scanner.async_next().await.with_context(|| path.to_string())? {
+                let cdx = match self.dedup_revisit(&scanned.record, db).await? {
+                    Some(revisit) => self.add_warc(&revisit).await?,
+                    None => self.add_gzip_member(scanned.raw.unwrap(), &scanned.record).await?
+                };
+                self.insert_in_db(&cdx, db).await?;
+                count += 1;
+            }
+
+            return Ok(count);
+        }
+
+        let mut reader = WarcReader::from_file(path).await?;
+        while let Some(record) = reader.async_next().await {
+            self.ingest_warc(&record, db).await?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// checks a WACZ package then appends its records and a `metadata` record per pages list,
+    /// `tmp_dir` receives the package's warc files while they are imported.
+    pub async fn import_wacz(&self, path: &str, tmp_dir: &str, db: Option<&DBManager>) -> anyhow::Result<usize> {
+        let mut wacz = WaczReader::open(path)?;
+        let mut count = 0;
+
+        for (i, warc_path) in wacz.warc_paths().into_iter().enumerate() {
+            let extracted = format!("{}/{}.{}", tmp_dir, i, warc_path.rsplit('/').next().unwrap_or_default());
+            wacz.extract(&warc_path, &extracted)?;
+
+            let ret = self.import_warc_file(&extracted, db).await;
+            fs::remove_file(&extracted).await?;
+            count += ret.with_context(|| warc_path.clone())?;
+        }
+
+        let name = path.rsplit('/').next().unwrap_or(path);
+        for record in wacz.page_records(name)? {
+            self.ingest_warc(&record, db).await?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// appends a tombstone to the index so the record
+    /// is not loaded back by `init_db` or `rebuild`, then deactivates it in the database (if any).
+    /// tombstones hold the record id, every copy of the record in the collection is deleted.
+    pub async fn delete_record(&self, record_id: &str, db: Option<&DBManager>) -> anyhow::Result<()> {
+        info!("deleting record from `{}`: {}", self.get_slug().await, record_id);
+
+        let _guard = self.write_lock.lock().await;
+        let index_path = format!("{}/index.cdx", self.path);
+        let seq = self.journal.begin(JournalOp::SyncDb).await?;
+        let ret = self.journaled_append(None, async {
+            self.fm.append(
+                &index_path,
+                format!("{}\n", CDXRecord::tombstone(record_id)).as_bytes()).await?;
+            self.fm.sync(&index_path).await
+        }).await;
+        if let Err(x) = ret {
+            self.journal.end(seq).await?;
+            return Err(x);
+        }
+
+        let uuid = self.get_uuid().await;
+        self.end_db_switch(seq, async {
+            if let Some(db) = db {
+                db.deactivate_record(&uuid, record_id).await?;
+            }
+            Ok(())
+        }.await).await?;
+
+        self.seal_index_if_needed().await
+    }
+
+    /// runs `write` under an `Append` journal entry, the write lock must be held.
+    /// a failed write is rolled back and its entry ended right away, since a pending
+    /// entry would truncate the appends made after it on the next load.
+    async fn journaled_append(&self, file: Option<&str>, write: impl Future<Output = anyhow::Result<()>>) -> anyhow::Result<()> {
+        if self.read_only.load(Ordering::Acquire) {
+            bail!("{}: a failed write could not be rolled back, the collection must be reloaded", self.get_slug().await);
+        }
+
+        let index_path = format!("{}/index.cdx", self.path);
+        let file_path = file.map(|f| format!("{}/{}", self.path, f));
+        let old_file_size = match &file_path {
+            Some(path) => file_size(path).await,
+            None => 0
+        };
+        let old_index_size = file_size(&index_path).await;
+        let seq = self.journal.begin(JournalOp::Append{
+            file: file.map(str::to_string),
+            file_size: old_file_size,
+            index_size: old_index_size}).await?;
+
+        let ret = match write.await {
+            Ok(()) => self.journal.end(seq).await,
+            Err(x) => {
+                warn!("{}: rolling back failed append: {:#}", self.path, x);
+                if let Err(y) = self.rollback_append(seq, file_path.as_deref(), old_file_size, old_index_size).await {
+                    warn!("{}: rollback failed: {:#}", self.path, y);
+                    self.read_only.store(true, Ordering::Release);
+                }
+                return Err(x);
+            }
+        };
+
+        /* the entry is still pending, so appending again would be undone on the next load */
+        if ret.is_err() {
+            self.read_only.store(true, Ordering::Release);
+        }
+        ret
+    }
+
+    async fn rollback_append(&self, seq: u64, file_path: Option<&str>, file_size: u64, index_size: u64) -> anyhow::Result<()> {
+        let index_path = format!("{}/index.cdx", self.path);
+        if let Some(file_path) = file_path {
+            truncate_file(file_path, file_size).await?;
+            self.fm.unmanage_file(file_path).await;
+        }
+        truncate_file(&index_path, index_size).await?;
+        self.fm.unmanage_file(&index_path).await;
+
+        self.journal.end(seq).await
+    }
+
+    /// moves `index.cdx` into a new compressed segment once it is big enough,
+    /// the write lock must be held.
+    async fn seal_index_if_needed(&self) -> anyhow::Result<()> {
+        let manifest = self.manifest.read().await.clone();
+        let index_path = format!("{}/index.cdx", self.path);
+        if manifest.index_segment_threshold == 0 || file_size(&index_path).await < manifest.index_segment_threshold {
+            return Ok(());
+        }
+
+        let segment_id = self.list_index_segments().await?
+            .iter()
+            .filter_map(|f| index_segment_number(f))
+            .max().unwrap_or(0) + 1;
+        let segment_name = format!("index.{}.cdx.{}", segment_id, manifest.index_compression);
+        info!("{}: sealing index into {}", manifest.slug, segment_name);
+
+        remove_staged_files(&self.path).await?;
+        let stage_seq = self.journal.begin(JournalOp::Stage).await?;
+
+        let header = format!("{}\n", CDX_HEADER);
+        let source = header.as_bytes().chain(BufReader::new(fs::File::open(&index_path).await?));
+        let mut output = fs::OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .open(format!("{}/.{}", self.path, segment_name)).await?;
+        if manifest.index_compression == "zst" {
+            tokio::io::copy(&mut ZstdEncoder::new(source), &mut output).await?;
+        } else {
+            tokio::io::copy(&mut GzipEncoder::new(source), &mut output).await?;
+        }
+        output.sync_all().await?;
+
+        self.switch(
+            vec![(format!(".{}", segment_name), segment_name)],
+            vec!["index.cdx".to_string()], None).await?;
+        self.journal.end(stage_seq).await
+    }
+
+    /// sealed index segments, oldest first.
+    async fn list_index_segments(&self) -> anyhow::Result<Vec<String>> {
+        let mut segments: Vec<(u32, String)> = Vec::new();
+        let mut dir_handle = fs::read_dir(&self.path).await?;
+        while let Some(f) = dir_handle.next_entry().await? {
+            let filename = f.file_name().to_string_lossy().to_string();
+            if let Some(n) = index_segment_number(&filename) {
+                segments.push((n, filename));
+            }
+        }
+
+        segments.sort();
+        Ok(segments.into_iter().map(|(_, f)| f).collect())
+    }
+
+    /// identifiers of every record marked as deleted in the index.
+    pub async fn get_tombstones(&self) -> anyhow::Result<HashSet<String>> {
+        let mut tombstones = HashSet::new();
+        let mut reader = self.iter_cdx().await?;
+
+        while let Some(x) = reader.async_next().await? {
+            if x.is_tombstone() {
+                tombstones.insert(x.get_record_id());
+            }
+        }
+
+        Ok(tombstones)
+    }
+
     async fn ensure_dict_loaded(&self) {
         let manifest = self.manifest.read().await;
         let dict = self.dict.read().await;
@@ -175,8 +484,17 @@ impl Collection {
 
     async fn compress(&self, content: Vec<u8>) -> Vec<u8> {
         let manifest = self.manifest.read().await;
-        if let None = manifest.dict_id {
-            return content
+        match manifest.compression.as_deref() {
+            None => return content,
+            Some("gzip") => {
+                let mut ret = Vec::new();
+                GzipEncoder::with_quality(
+                    &content[..],
+                    async_compression::Level::Precise(manifest.compression_level))
+                    .read_to_end(&mut ret).await.expect("unable to compress record");
+                return ret
+            },
+            _ => {}
         }
         
         self.ensure_dict_loaded().await;
@@ -194,9 +512,19 @@ impl Collection {
         ret
     }
 
+    /// reads sealed segments then `index.cdx`.
     pub async fn iter_cdx(&self) -> anyhow::Result<CDXFileReader> {
-        // TODO: cdx.gz
-        Ok(CDXFileReader::open(&format!("{}/index.cdx", self.path)).await?)
+        let mut files: Vec<String> = self.list_index_segments().await?
+            .into_iter()
+            .map(|f| format!("{}/{}", self.path, f))
+            .collect();
+
+        let index_path = format!("{}/index.cdx", self.path);
+        if fs::metadata(&index_path).await.is_ok() {
+            files.push(index_path);
+        }
+
+        CDXFileReader::open_all(files).await
     }
 
     pub async fn get_record(&self, filename: &str, offset: i64) -> anyhow::Result<Option<WarcRecord>>{
@@ -205,22 +533,82 @@ impl Collection {
 
         lfp.seek(SeekFrom::Start(offset as u64)).await?;
 
-        let manifest = self.manifest.read().await;
+        self.read_stored_record(&mut *lfp).await
+    }
 
-        if let Some(_) = manifest.compression {
-            self.ensure_dict_loaded().await;
-            let dict = self.dict.read().await;
-            let vec = dict.as_ref().unwrap();
-            Ok(read_record(BufReader::new(
-                Box::new(
-                    ZstdDecoder::with_dict(
-                        &mut *lfp,
-                        &vec[..]
-                    ).expect(&format!("unable to load dictionary {}", manifest.dict_id.unwrap()))
-                )
-            )).await?)
-        } else {
-            Ok(read_record(&mut *lfp).await?)
+    /// adds every live record to a WACZ package, returns how many were added.
+    pub async fn export_wacz<W: std::io::Write + std::io::Seek>(&self, wacz: &mut WaczWriter<W>) -> anyhow::Result<usize> {
+        let tombstones = self.get_tombstones().await?;
+        /* deleted records may still be the original of a live revisit */
+        let mut deleted: HashMap<String, (String, i64)> = HashMap::new();
+        let mut reader = self.iter_cdx().await?;
+        let mut count = 0;
+
+        while let Some(cdx) = reader.async_next().await? {
+            if cdx.is_tombstone() {
+                continue;
+            }
+
+            let (Some(filename), Some(offset)) = (cdx.get_file_name(), cdx.get_file_offset()) else {
+                continue;
+            };
+
+            if tombstones.contains(&cdx.get_record_id()) {
+                deleted.insert(cdx.get_record_id(), (filename, offset));
+                continue;
+            }
+
+            match self.get_record(&filename, offset).await? {
+                Some(record) => {
+                    let record = self.expand_orphan_revisit(record, &deleted).await?;
+                    wacz.add_record(&record).await?;
+                    count += 1;
+                },
+                None => warn!("{}: record {} not found at {}:{}", self.path, cdx.get_record_id(), filename, offset)
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// the response `record` stands for if it is a revisit of a deleted record,
+    /// so exports don't hold revisits of payloads they lack.
+    async fn expand_orphan_revisit(&self, record: WarcRecord, deleted: &HashMap<String, (String, i64)>) -> anyhow::Result<WarcRecord> {
+        if !is_identical_payload_revisit(&record) {
+            return Ok(record);
+        }
+
+        let location = record.get_header("WARC-Refers-To")
+            .and_then(|id| deleted.get(id.trim_matches(|x| "<>".contains(x))));
+        let Some((filename, offset)) = location else {
+            return Ok(record);
+        };
+
+        match self.get_record(filename, *offset).await? {
+            Some(original) => resolve_revisit(&record, &original),
+            None => Ok(record)
+        }
+    }
+
+    /// reads a record stored with the collection's compression.
+    async fn read_stored_record<R: AsyncBufRead + Unpin + Send>(&self, reader: R) -> anyhow::Result<Option<WarcRecord>> {
+        match self.record_compression().await {
+            RecordCompression::None => read_record(reader).await,
+            RecordCompression::Gzip => read_record(BufReader::new(GzipDecoder::new(reader))).await,
+            RecordCompression::Zstd(dict) => read_record(BufReader::new(
+                ZstdDecoder::with_dict(reader, &dict[..])?)).await
+        }
+    }
+
+    async fn record_compression(&self) -> RecordCompression {
+        let compression = self.manifest.read().await.compression.clone();
+        match compression.as_deref() {
+            None => RecordCompression::None,
+            Some("gzip") => RecordCompression::Gzip,
+            Some(_) => {
+                self.ensure_dict_loaded().await;
+                RecordCompression::Zstd(self.dict.read().await.clone().unwrap())
+            }
         }
     }
 
@@ -237,31 +625,129 @@ impl Collection {
         Ok(raw)
     }
 
-    // TODO: atomic
     pub async fn delete(&mut self) -> anyhow::Result<()> {
+        // never ended, so an interrupted deletion is finished on next load.
+        self.journal.begin(JournalOp::Delete).await?;
         fs::remove_dir_all(&self.path).await?;
         Ok(())
     }
 
-    // TODO: atomic switch
-    // TODO: prevent 2 rebuild at the same time
-    // TODO: keep flags
+    /// reads a record the way readers do and checks it matches its index entry.
+    pub async fn check_record(&self, cdx: &CDXRecord) -> anyhow::Result<Option<(IssueKind, String)>> {
+        let manifest = self.manifest.read().await.clone();
+
+        let (filename, offset, size) = match (cdx.get_file_name(), cdx.get_file_offset(), cdx.get_raw_size()) {
+            (Some(f), Some(o), Some(s)) => (f, o as u64, s),
+            _ => return Ok(Some((IssueKind::InvalidIndexEntry, "missing file, offset or size".to_string())))
+        };
+
+        let actual_size = match fs::metadata(format!("{}/{}", self.path, filename)).await {
+            Ok(m) => m.len(),
+            Err(_) => return Ok(Some((IssueKind::MissingFile, filename)))
+        };
+
+        if offset + size > actual_size {
+            return Ok(Some((IssueKind::Truncated,
+                format!("{}: record ends at {} but the file is {} bytes", filename, offset + size, actual_size))));
+        }
+
+        let raw = self.get_raw_record(&filename, offset as i64, size as usize).await?;
+        let content = if let Some(dict_id) = manifest.dict_id {
+            if !filename.contains(&format!(".{}.warc", dict_id)) {
+                return Ok(Some((IssueKind::DictionaryMismatch,
+                    format!("{} is not stored with dictionary {}", filename, dict_id))));
+            }
+
+            let frame_dict = zstd::zstd_safe::get_dict_id_from_frame(&raw).map(|d| d.get());
+            if frame_dict != Some(dict_id) {
+                return Ok(Some((IssueKind::DictionaryMismatch,
+                    format!("frame uses dictionary {:?} instead of {}", frame_dict, dict_id))));
+            }
+
+            match zstd::zstd_safe::find_frame_compressed_size(&raw) {
+                Ok(n) if n == raw.len() => {},
+                Ok(n) => return Ok(Some((IssueKind::SizeMismatch,
+                    format!("frame is {} bytes but {} are indexed", n, raw.len())))),
+                Err(_) => return Ok(Some((IssueKind::Unreadable, "invalid zstd frame".to_string())))
+            }
+
+            self.ensure_dict_loaded().await;
+            let dict = self.dict.read().await;
+            let mut content = Vec::new();
+            let decoded = zstd::stream::read::Decoder::with_dictionary(&raw[..], &dict.as_ref().unwrap()[..])
+                .and_then(|mut d| std::io::Read::read_to_end(&mut d, &mut content));
+            if let Err(x) = decoded {
+                return Ok(Some((IssueKind::Unreadable, format!("unable to decompress: {}", x))));
+            }
+            content
+        } else if manifest.compression.as_deref() == Some("gzip") {
+            let mut member = CountingReader::new(&raw[..]);
+            let mut content = Vec::new();
+            if let Err(x) = GzipDecoder::new(&mut member).read_to_end(&mut content).await {
+                return Ok(Some((IssueKind::Unreadable, format!("unable to decompress: {}", x))));
+            }
+
+            if member.position() != raw.len() as u64 {
+                return Ok(Some((IssueKind::SizeMismatch,
+                    format!("gzip member is {} bytes but {} are indexed", member.position(), raw.len()))));
+            }
+            content
+        } else {
+            raw
+        };
+
+        let mut remaining = &content[..];
+        match read_record(&mut remaining).await {
+            Ok(Some(record)) => {
+                if !remaining.is_empty() {
+                    return Ok(Some((IssueKind::SizeMismatch,
+                        format!("{} trailing bytes after the record", remaining.len()))));
+                }
+
+                let record_id = record.get_record_id().unwrap_or_default();
+                if record_id != cdx.get_record_id() {
+                    return Ok(Some((IssueKind::IdMismatch, format!("stored record is {}", record_id))));
+                }
+            },
+            Ok(None) => return Ok(Some((IssueKind::Unreadable, "empty record".to_string()))),
+            Err(x) => return Ok(Some((IssueKind::Unreadable, x.to_string())))
+        }
+
+        Ok(None)
+    }
+
+    /// names of the record files present in the collection directory.
+    pub async fn list_record_files(&self) -> anyhow::Result<Vec<String>> {
+        let mut ret = Vec::new();
+        let mut dir_handle = fs::read_dir(&self.path).await?;
+        while let Some(f) = dir_handle.next_entry().await? {
+            let filename = f.file_name().to_string_lossy().to_string();
+            if filename.starts_with("records.") {
+                ret.push(filename);
+            }
+        }
+
+        ret.sort();
+        Ok(ret)
+    }
+
+    /// records deleted in the index or in the database are dropped.
     // TODO: manage rebuilding with the same dict
     // TODO: support not compressed rebuild
     pub async fn rebuild(&self, dict: Option<(String, u32)>, db: &DBManager) -> anyhow::Result<()> {
+        let _guard = self.write_lock.lock().await;
         let manifest = self.manifest.read().await;
         let dict_id = dict.unwrap().1; // TODO: check Some(dict)
-        let mut old_dict: Option<tokio::sync::RwLockReadGuard<Option<Arc<Vec<u8>>>>> = None;
-
-        if let Some(_) = manifest.compression {
-            self.ensure_dict_loaded().await;
-            old_dict = Some(self.dict.read().await);
-        }
 
         /*  enumerate records because the underlying file could be corrupted
             since it might be zero'd to delete specific records or whatever reason
             so i prefer to rely on record index */
         info!("enumerating '{}' records...", manifest.slug);
+        let mut tombstones = self.get_tombstones().await?;
+        tombstones.extend(db.get_inactive_identifiers(
+            &manifest.uuid,
+            manifest.dict_id.map(|e| e as i64),
+            manifest.compression.as_deref()).await?);
         let mut reader = self.iter_cdx().await?;
 
         /*  store separately record files to optimise memory 
@@ -269,7 +755,12 @@ impl Collection {
         let mut record_files: Vec<String> = Vec::new();
         let mut records: Vec<(u16,u64)> = Vec::new();
 
-        while let Some(x) = reader.async_next().await {
+        while let Some(x) = reader.async_next().await? {
+            // deleted records are dropped for good
+            if x.is_tombstone() || tombstones.contains(&x.get_record_id()) {
+                continue;
+            }
+
             let file_id: usize;
             let filename = x.get_file_name().unwrap();
             if let Some(offset) = record_files.iter().position(|f| *f == filename) {
@@ -291,11 +782,11 @@ impl Collection {
             in case some partial rebuild got interrupted */
         debug!("cleaning partial build");
         db.delete_records(
-            &manifest.slug,
+            &manifest.uuid,
             Some(dict_id as i64),
             Some("zstd")).await?;
-        let _ = fs::remove_dir(format!("{}/.index.cdx", self.path)).await;
-        // TODO: delete files too
+        remove_staged_files(&self.path).await?;
+        let stage_seq = self.journal.begin(JournalOp::Stage).await?;
 
         // TODO: check Some(dict)
         debug!("loading new dictionary...");
@@ -314,36 +805,22 @@ impl Collection {
         let mut output_fp = fs::OpenOptions::new()
             .create_new(true)
             .append(true)
-            .open(format!("{}/{}", self.path, output_file_name)).await.expect("unable to open dst file");
+            .open(format!("{}/.{}", self.path, output_file_name)).await.expect("unable to open dst file");
 
-        let mut fp_id: u16 = records[0].0;
-        let mut fp_mutex = self.fm.get_file(&format!("{}/{}", self.path, &record_files[fp_id as usize])).await?;
-        let mut fp = fp_mutex.lock().await;
+        let mut fp_id: Option<u16> = None;
+        let mut fp_mutex = None;
         for r in records {
-            if fp_id != r.0 {
-                drop(fp);
-                fp_mutex = self.fm.get_file(&format!("{}/{}", self.path, &record_files[r.0 as usize])).await?;
-                fp = fp_mutex.lock().await;
-                fp_id = r.0;
-                
+            if fp_id != Some(r.0) {
+                fp_mutex = Some(self.fm.get_file(&format!("{}/{}", self.path, &record_files[r.0 as usize])).await?);
+                fp_id = Some(r.0);
             }
 
-            fp.seek(SeekFrom::Start(r.1)).await?;
-            let record: Option<WarcRecord>;
+            let record = {
+                let mut fp = fp_mutex.as_ref().unwrap().lock().await;
+                fp.seek(SeekFrom::Start(r.1)).await?;
+                self.read_stored_record(&mut *fp).await?
+            };
 
-            if let Some(ref dict) = old_dict {
-                record = read_record(BufReader::new(
-                    Box::new(
-                        ZstdDecoder::with_dict(
-                            &mut *fp,
-                            &dict.as_ref().unwrap()[..],
-                        ).expect(&format!("unable to load dictionary {}", manifest.dict_id.unwrap()))
-                    )
-                )).await?
-            } else {
-                record = read_record(&mut *fp).await?
-            }
-            
             if let Some(record) = record {
                 let content = record.serialize();
                 let mut cdxr = CDXRecord::from_warc(&record)?;
@@ -357,12 +834,13 @@ impl Collection {
                 encoder.unwrap().read_to_end(&mut compressed).await.expect("unable to compress record");
                 
                 if (output_fp.stream_position().await? + (compressed.len() as u64)) > manifest.split_threshold {
+                    output_fp.sync_all().await?;
                     output_file_id += 1;
                     output_file_name = format!("records.{}.{}.warc.zstd", output_file_id, dict_id);
                     output_fp = fs::OpenOptions::new()
                         .create_new(true)
                         .append(true)
-                        .open(&format!("{}/{}", self.path, output_file_name)).await
+                        .open(&format!("{}/.{}", self.path, output_file_name)).await
                         .expect("unable to open dst file");
                 }
                 cdxr.set_file(
@@ -381,63 +859,326 @@ impl Collection {
             }
         }
 
+        output_fp.sync_all().await?;
+        output_index.sync_all().await?;
+
         info!("commiting rebuild...");
-        db.activate_records(&manifest.uuid,
-            Some(dict_id as i64),
-            Some("zstd")).await?;
-        db.delete_records(&manifest.uuid,
-            manifest.dict_id.map(|e| e as i64),
-            manifest.compression.as_deref()).await?;
+        let mut renames: Vec<(String, String)> = (1..=output_file_id)
+            .map(|i| {
+                let name = format!("records.{}.{}.warc.zstd", i, dict_id);
+                (format!(".{}", name), name)
+            }).collect();
+        renames.push((".index.cdx".to_string(), "index.cdx".to_string()));
+
+        let mut removals: Vec<String> = Vec::new();
+        for i in 1.. {
+            let target_file = self.gen_warc_filename(i).await;
 
-        let old_dict_id = manifest.dict_id;
-        let old_algo = manifest.compression.clone();
+            if fs::metadata(format!("{}/{}", self.path, target_file)).await.is_err() {
+                break;
+            }
 
+            removals.push(target_file);
+        }
+        removals.extend(self.list_index_segments().await?);
+
+        let uuid = manifest.uuid.clone();
+        let old_dict_id = manifest.dict_id.map(|e| e as i64);
+        let old_compression = manifest.compression.clone();
         drop(manifest);
         let mut manifest = self.manifest.write().await;
         manifest.dict_id = Some(dict_id);
         manifest.compression = Some("zstd".to_string()); // TODO:
-        self.flush_manifest(&manifest).await;
 
-        for i in 1.. {
-            let target_file = if let Some(algo) = &old_algo {
-                format!(
-                        "{}/records.{}.{}.warc.{}",
-                        self.path,
-                        i,
-                        old_dict_id.unwrap(),
-                        algo)
+        let switch_seq = self.begin_switch(renames, removals, Some(manifest.clone()), true).await?;
+        self.journal.end(stage_seq).await?;
+        drop(manifest);
+
+        let db_ret: anyhow::Result<()> = async {
+            db.activate_records(&uuid, Some(dict_id as i64), Some("zstd")).await?;
+            db.delete_records(&uuid, old_dict_id, old_compression.as_deref()).await
+        }.await;
+        self.end_db_switch(switch_seq, db_ret).await?;
+
+        // reset dict to not use the old one with new records
+        drop(dict);
+        *self.dict.write().await = None;
+        *self.cur_record_file.write().await = 1;
+
+        Ok(())
+    }
+
+    /// writes the live records and their index to staged files,
+    /// fails on any unreadable index entry so nothing gets dropped silently.
+    async fn stage_compaction(&self, manifest: &CollectionManifest, tombstones: &HashSet<String>) -> anyhow::Result<(Vec<CDXRecord>, u32, u64)> {
+        let mut output_file_id = 1;
+        let mut output_file_name = self.gen_warc_filename(output_file_id).await;
+        let mut output_fp = fs::OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(format!("{}/.{}", self.path, output_file_name)).await?;
+        let mut output_index = fs::OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(format!("{}/.index.cdx", self.path)).await?;
+
+        let mut records: Vec<CDXRecord> = Vec::new();
+        let mut dropped: u64 = 0;
+        let mut reader = self.iter_cdx().await?;
+        while let Some(mut cdxr) = reader.async_next().await? {
+            if cdxr.is_tombstone() {
+                continue;
+            }
+
+            if tombstones.contains(&cdxr.get_record_id()) {
+                dropped += 1;
+                continue;
+            }
+
+            let filename = cdxr.get_file_name().unwrap();
+            let offset = cdxr.get_file_offset().unwrap();
+            let raw = if let Some(size) = cdxr.get_raw_size() {
+                self.get_raw_record(&filename, offset, size as usize).await?
+            } else if let Some(record) = self.get_record(&filename, offset).await? {
+                self.compress(record.serialize()).await
             } else {
-                format!("{}/records.{}.warc", self.path, i)
+                bail!("unable to read record {} while compacting", cdxr.get_record_id());
             };
 
-            if let Err(_) = fs::metadata(&target_file).await {
+            let position = output_fp.stream_position().await?;
+            if position > 0 && (position + (raw.len() as u64)) > manifest.split_threshold {
+                output_fp.sync_all().await?;
+                output_file_id += 1;
+                output_file_name = self.gen_warc_filename(output_file_id).await;
+                output_fp = fs::OpenOptions::new()
+                    .create_new(true)
+                    .append(true)
+                    .open(format!("{}/.{}", self.path, output_file_name)).await?;
+            }
+
+            cdxr.set_file(
+                output_file_name.clone(),
+                Some(output_fp.stream_position().await?),
+                Some(raw.len() as u64));
+            output_fp.write_all(&raw).await?;
+            output_index.write_all(format!("{}\n", cdxr).as_bytes()).await?;
+            records.push(cdxr);
+        }
+        output_fp.sync_all().await?;
+        output_index.sync_all().await?;
+
+        Ok((records, output_file_id, dropped))
+    }
+
+    /// rewrites record files with active records only, keeping
+    /// the current dictionary and compression. raw records are copied
+    /// as-is, so nothing is decompressed unless its size is unknown.
+    pub async fn compact(&mut self, db: &DBManager) -> anyhow::Result<()> {
+        let manifest = self.manifest.read().await.clone();
+
+        info!("compacting '{}'...", manifest.slug);
+        let tombstones = self.get_tombstones().await?;
+
+        debug!("cleaning partial compaction");
+        remove_staged_files(&self.path).await?;
+        let stage_seq = self.journal.begin(JournalOp::Stage).await?;
+
+        let (records, output_file_id, dropped) = match self.stage_compaction(&manifest, &tombstones).await {
+            Ok(staged) => staged,
+            Err(x) => {
+                // nothing was switched yet, leave the collection and the database as they were
+                remove_staged_files(&self.path).await?;
+                self.journal.end(stage_seq).await?;
+                return Err(x.context("compaction aborted"));
+            }
+        };
+
+        info!("commiting compaction ({} record(s) dropped)...", dropped);
+        let mut renames: Vec<(String, String)> = Vec::new();
+        let mut removals: Vec<String> = Vec::new();
+        for i in 1.. {
+            let target_file = self.gen_warc_filename(i).await;
+
+            if i <= output_file_id {
+                renames.push((format!(".{}", target_file), target_file));
+            } else if fs::metadata(format!("{}/{}", self.path, target_file)).await.is_ok() {
+                removals.push(target_file);
+            } else {
                 break;
             }
+        }
+        renames.push((".index.cdx".to_string(), "index.cdx".to_string()));
+        removals.extend(self.list_index_segments().await?);
+
+        let switch_seq = self.begin_switch(renames, removals, None, true).await?;
+        self.journal.end(stage_seq).await?;
+        *self.cur_record_file.write().await = 1;
+
+        let db_ret = db.replace_collection_records(
+            &manifest.uuid, &records, &[],
+            manifest.dict_id.map(|e| e as i64),
+            manifest.compression.as_deref()).await;
+        self.end_db_switch(switch_seq, db_ret).await
+    }
 
-            self.fm.unmanage_file(&target_file).await;
-            fs::remove_file(target_file).await?;
+    /// regenerates `index.cdx` from the record files, deletions are kept
+    /// from the old index (if still readable) and from the database.
+    /// when `db` is given, its rows are replaced by the scanned records.
+    pub async fn reindex(&self, db: Option<&DBManager>) -> anyhow::Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let manifest = self.manifest.read().await.clone();
+
+        info!("reindexing '{}'...", manifest.slug);
+        let mut tombstones = match self.get_tombstones().await {
+            Ok(t) => t,
+            Err(x) => {
+                warn!("unable to read the current index ({}), deletions only come from the database", x);
+                HashSet::new()
+            }
+        };
+        if let Some(db) = db {
+            tombstones.extend(db.get_inactive_identifiers(
+                &manifest.uuid,
+                manifest.dict_id.map(|e| e as i64),
+                manifest.compression.as_deref()).await?);
         }
 
-        // reset dict to not use the old one with new records
-        drop(dict);
-        *self.dict.write().await = None;
-        
-        // TODO: check if the file exist instead of ignoring errors
-        let _ = fs::remove_file(format!("{}/index.cdx.gz", self.path)).await;
-        fs::rename(
-            format!("{}/.index.cdx", self.path),
-            format!("{}/index.cdx", self.path)).await?;
-        Ok(())
+        debug!("cleaning partial reindexing");
+        remove_staged_files(&self.path).await?;
+        let stage_seq = self.journal.begin(JournalOp::Stage).await?;
+
+        let mut output_index = fs::OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(format!("{}/.index.cdx", self.path)).await?;
+
+        let compression = self.record_compression().await;
+
+        let mut records: Vec<CDXRecord> = Vec::new();
+        for i in 1.. {
+            let filename = self.gen_warc_filename(i).await;
+            let path = format!("{}/{}", self.path, filename);
+            if fs::metadata(&path).await.is_err() {
+                break;
+            }
+
+            debug!("scanning {}...", filename);
+            let mut scanner = WarcFileScanner::open(&path, compression.clone()).await?;
+            loop {
+                let ScannedRecord{offset, size, record, ..} = match scanner.async_next().await {
+                    Ok(Some(x)) => x,
+                    Ok(None) => break,
+                    Err(x) => {
+                        warn!("{}: {:#}, following bytes are ignored", filename, x);
+                        break;
+                    }
+                };
+
+                let mut cdxr = match CDXRecord::from_warc(&record) {
+                    Ok(c) => c,
+                    Err(x) => {
+                        warn!("{}: record at offset {} cannot be indexed: {}", filename, offset, x);
+                        continue;
+                    }
+                };
+                cdxr.set_file(filename.clone(), Some(offset), Some(size));
+                output_index.write_all(format!("{}\n", cdxr).as_bytes()).await?;
+                records.push(cdxr);
+            }
+        }
+
+        let (deleted, live): (Vec<CDXRecord>, Vec<CDXRecord>) = records.into_iter()
+            .partition(|r| tombstones.contains(&r.get_record_id()));
+        for cdxr in &deleted {
+            output_index.write_all(format!("{}\n", CDXRecord::tombstone(&cdxr.get_record_id())).as_bytes()).await?;
+        }
+        output_index.sync_all().await?;
+
+        info!("commiting reindexing ({} record(s), {} deleted)...", live.len() + deleted.len(), deleted.len());
+        let switch_seq = self.begin_switch(
+            vec![(".index.cdx".to_string(), "index.cdx".to_string())],
+            self.list_index_segments().await?, None, db.is_some()).await?;
+        self.journal.end(stage_seq).await?;
+
+        match db {
+            Some(db) => {
+                let db_ret = db.replace_collection_records(
+                    &manifest.uuid, &live, &deleted,
+                    manifest.dict_id.map(|e| e as i64),
+                    manifest.compression.as_deref()).await;
+                self.end_db_switch(switch_seq, db_ret).await
+            },
+            None => self.journal.end(switch_seq).await
+        }
+    }
+
+    /// replaces collection files with staged ones,
+    /// once started, it is finished even if interrupted.
+    async fn switch(&self, renames: Vec<(String, String)>, removals: Vec<String>, manifest: Option<CollectionManifest>) -> anyhow::Result<()> {
+        let seq = self.begin_switch(renames, removals, manifest, false).await?;
+        self.journal.end(seq).await
+    }
+
+    /// like `switch` but the journal entry is left open, `resync_db` is set
+    /// when the database is updated afterwards (see `end_db_switch`).
+    async fn begin_switch(&self, renames: Vec<(String, String)>, removals: Vec<String>, manifest: Option<CollectionManifest>, resync_db: bool) -> anyhow::Result<u64> {
+        let op = JournalOp::Switch{renames, removals, manifest, resync_db};
+        let seq = self.journal.begin(op.clone()).await?;
+        apply_switch(&self.path, &op).await?;
+
+        /* file handles still point to the replaced files */
+        if let JournalOp::Switch{renames, removals, ..} = &op {
+            for f in renames.iter().map(|(_, dst)| dst).chain(removals.iter()) {
+                self.fm.unmanage_file(&format!("{}/{}", self.path, f)).await;
+            }
+        }
+
+        Ok(seq)
     }
 
-    // must be called when self.manifest is locked
-    async fn flush_manifest(&self, manifest: &CollectionManifest) {
-        let manifest_str = serde_json::to_string(manifest)
-            .expect("unable to serialize collection manifest");
+    /// ends a switch started with `resync_db` (or a `SyncDb` entry), files are
+    /// already switched so a failed database update flags the collection for a resync.
+    async fn end_db_switch(&self, seq: u64, db_ret: anyhow::Result<()>) -> anyhow::Result<()> {
+        if db_ret.is_err() {
+            flag_db_resync(&self.path).await?;
+        }
+        self.journal.end(seq).await?;
+        db_ret
+    }
 
-        // TODO: atomicity (if the disk doesn't have enough space, we can get corrupted manifest)
-        fs::write(format!("{}/manifest.json", self.path), manifest_str).await
-            .expect("failed to write collection manifest");
+    /// true when an interrupted switch left the database out of sync.
+    pub async fn needs_db_resync(&self) -> bool {
+        fs::metadata(format!("{}/{}", self.path, DB_RESYNC_FLAG)).await.is_ok()
+    }
+
+    /// replaces the collection rows in the database by the content of the index.
+    pub async fn resync_db(&self, db: &DBManager) -> anyhow::Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let manifest = self.manifest.read().await.clone();
+
+        info!("resyncing '{}' database records...", manifest.slug);
+        let tombstones = self.get_tombstones().await?;
+        let mut live: Vec<CDXRecord> = Vec::new();
+        let mut deleted: Vec<CDXRecord> = Vec::new();
+        let mut reader = self.iter_cdx().await?;
+        while let Some(cdxr) = reader.async_next().await? {
+            if cdxr.is_tombstone() {
+                continue;
+            }
+
+            if tombstones.contains(&cdxr.get_record_id()) {
+                deleted.push(cdxr);
+            } else {
+                live.push(cdxr);
+            }
+        }
+
+        db.replace_collection_records(
+            &manifest.uuid, &live, &deleted,
+            manifest.dict_id.map(|e| e as i64),
+            manifest.compression.as_deref()).await?;
+        fs::remove_file(format!("{}/{}", self.path, DB_RESYNC_FLAG)).await?;
+        Ok(())
     }
 
     pub async fn get_dict(&self) -> (Option<u32>, Option<String>) {
@@ -456,6 +1197,14 @@ impl Collection {
 
 pub async fn load_collection(collection_path: &str, dict_store: Arc<DictStore>) -> Result<Collection> {
     debug!("loading collection: {}", collection_path);
+    debug!("replaying journal...");
+    let (journal, pending_ops) = Journal::open(&format!("{}/journal", collection_path)).await?;
+    if !pending_ops.is_empty() {
+        warn!("{}: recovering {} interrupted operation(s)", collection_path, pending_ops.len());
+        recover(collection_path, &pending_ops).await?;
+    }
+    journal.clear().await?;
+
     debug!("reading manifest...");
     let manifest: CollectionManifest = serde_json::from_slice(
         &fs::read(format!("{}/manifest.json", collection_path)).await?)?;
@@ -471,6 +1220,7 @@ pub async fn load_collection(collection_path: &str, dict_store: Arc<DictStore>)
 
     let collection = Collection{
         fm: FileManager::new(),
+        journal, write_lock: Mutex::new(()), read_only: AtomicBool::new(false),
         path: collection_path.to_string(),
         manifest: RwLock::new(manifest),
         dict_store, dict: RwLock::new(None),
@@ -484,40 +1234,36 @@ pub async fn load_collection(collection_path: &str, dict_store: Arc<DictStore>)
 pub async fn create_collection(
     repository_path: &str,
     slug: &str,
-    dictionary: Option<(String, u32)>,
+    compression: Option<String>,
+    dict_id: Option<u32>,
+    dedup: bool,
     dict_store: Arc<DictStore>
     ) -> Result<Collection>{
     debug!("creating collection: {}", slug);
     let collection_uuid = Uuid::new_v4().to_string();
     let collection_path = format!("{}/{}/", repository_path, collection_uuid);
 
-    if let Some(x) = &dictionary {
+    if let Some(x) = dict_id {
         // assuming it is zstd.
-        if !dict_store.has_zstd_dict(x.1).await {
+        if !dict_store.has_zstd_dict(x).await {
             bail!("no such dictionary");
         }
     }
 
-    let manifest = serde_json::to_string(&CollectionManifest{
+    let manifest = CollectionManifest{
         uuid: collection_uuid,
         slug: slug.to_string(),
-        compression_level: zstd::DEFAULT_COMPRESSION_LEVEL,  // TODO: configure
+        compression_level: if compression.as_deref() == Some("gzip") {6} else {zstd::DEFAULT_COMPRESSION_LEVEL},  // TODO: configure
         split_threshold: (1 << 32) - 1, // TODO: configure
-        dict_id: if let Some(x) = &dictionary {
-            Some(x.1)
-        } else {
-            None
-        },
-        compression: if let Some(x) = dictionary {
-            Some(x.0)
-        } else {
-            None
-        }})?;
-    
-    // TODO: manifest.validate()
+        index_segment_threshold: default_index_segment_threshold(),
+        index_compression: default_index_compression(),
+        dict_id, compression, dedup};
+
+    manifest.validate().await?;
 
     fs::create_dir(&collection_path).await?;
-    fs::write(format!("{}/manifest.json", collection_path), &manifest).await?;
+    write_manifest(&collection_path, &manifest).await?;
+    let manifest = serde_json::to_string(&manifest)?;
     let coll = load_collection(&collection_path, dict_store).await?;
 
     let mut first_record = WarcRecord::new("warcinfo".to_string());
@@ -533,4 +1279,215 @@ pub async fn create_collection(
 
     debug!("collection created!");
     Ok(coll)
-}
\ No newline at end of file
+}
+
+async fn file_size(path: &str) -> u64 {
+    fs::metadata(path).await.map(|m| m.len()).unwrap_or(0)
+}
+
+/// writes the manifest to a temporary file then renames it,
+/// so a crash can't leave a truncated manifest.
+async fn write_manifest(collection_path: &str, manifest: &CollectionManifest) -> anyhow::Result<()> {
+    let manifest_str = serde_json::to_string(manifest)?;
+    let tmp_path = format!("{}/.manifest.json", collection_path);
+
+    let mut fp = fs::File::create(&tmp_path).await?;
+    fp.write_all(manifest_str.as_bytes()).await?;
+    fp.sync_all().await?;
+    drop(fp);
+
+    fs::rename(tmp_path, format!("{}/manifest.json", collection_path)).await?;
+    Ok(())
+}
+
+/// `index.N.cdx.gz` -> `N`
+fn index_segment_number(filename: &str) -> Option<u32> {
+    let (n, ext) = filename.strip_prefix("index.")?.split_once(".cdx.")?;
+    if ext != "gz" && ext != "zst" {
+        return None;
+    }
+    n.parse().ok()
+}
+
+/// removes files left behind by an interrupted rebuild, compaction or seal.
+async fn remove_staged_files(collection_path: &str) -> anyhow::Result<()> {
+    let mut dir_handle = fs::read_dir(collection_path).await?;
+    while let Some(f) = dir_handle.next_entry().await? {
+        let filename = f.file_name().to_string_lossy().to_string();
+        if filename.starts_with(".records.") || filename.starts_with(".index.") || filename == ".manifest.json" {
+            debug!("removing staged file {}", filename);
+            fs::remove_file(f.path()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// created when the database may not match the collection files anymore.
+const DB_RESYNC_FLAG: &str = "db_resync";
+
+async fn flag_db_resync(collection_path: &str) -> anyhow::Result<()> {
+    fs::File::create(format!("{}/{}", collection_path, DB_RESYNC_FLAG)).await?
+        .sync_all().await?;
+    Ok(())
+}
+
+/// must be idempotent since it is replayed after a crash.
+async fn apply_switch(collection_path: &str, op: &JournalOp) -> anyhow::Result<()> {
+    if let JournalOp::Switch{renames, removals, manifest, ..} = op {
+        for (src, dst) in renames {
+            let src = format!("{}/{}", collection_path, src);
+            if fs::metadata(&src).await.is_ok() {
+                fs::rename(src, format!("{}/{}", collection_path, dst)).await?;
+            }
+        }
+
+        if let Some(manifest) = manifest {
+            write_manifest(collection_path, manifest).await?;
+        }
+
+        for f in removals {
+            match fs::remove_file(format!("{}/{}", collection_path, f)).await {
+                Err(x) if x.kind() != std::io::ErrorKind::NotFound => return Err(x.into()),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// rolls forward committed operations then rolls back the unfinished ones.
+async fn recover(collection_path: &str, ops: &[JournalOp]) -> anyhow::Result<()> {
+    for op in ops {
+        match op {
+            JournalOp::Delete => {
+                info!("{}: finishing deletion", collection_path);
+                fs::remove_dir_all(collection_path).await?;
+                bail!("{}: collection has been deleted", collection_path);
+            },
+            JournalOp::SyncDb => {
+                warn!("{}: database may be out of sync with the files", collection_path);
+                flag_db_resync(collection_path).await?;
+            },
+            JournalOp::Switch{resync_db, ..} => {
+                info!("{}: finishing file switch", collection_path);
+                apply_switch(collection_path, op).await?;
+                if *resync_db {
+                    warn!("{}: database may be out of sync with the files", collection_path);
+                    flag_db_resync(collection_path).await?;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    for op in ops.iter().rev() {
+        match op {
+            JournalOp::Append{file, file_size, index_size} => {
+                info!("{}: rolling back append", collection_path);
+                if let Some(file) = file {
+                    truncate_file(&format!("{}/{}", collection_path, file), *file_size).await?;
+                }
+                truncate_file(&format!("{}/index.cdx", collection_path), *index_size).await?;
+            },
+            JournalOp::Stage => {
+                info!("{}: removing staged files", collection_path);
+                remove_staged_files(collection_path).await?;
+            },
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+async fn truncate_file(path: &str, size: u64) -> anyhow::Result<()> {
+    if file_size(path).await > size {
+        let fp = fs::OpenOptions::new().write(true).open(path).await?;
+        fp.set_len(size).await?;
+        fp.sync_all().await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn empty_collection() -> (String, Arc<DictStore>) {
+        let path = format!("{}/masstuffy-test-{}", std::env::temp_dir().display(), Uuid::new_v4());
+        fs::create_dir(&path).await.unwrap();
+        let manifest = CollectionManifest{
+            uuid: Uuid::new_v4().to_string(),
+            slug: "test".to_string(),
+            compression: None,
+            compression_level: 0,
+            dict_id: None,
+            split_threshold: (1 << 32) - 1,
+            index_segment_threshold: 0,
+            index_compression: default_index_compression(),
+            dedup: false};
+        write_manifest(&path, &manifest).await.unwrap();
+        (path.clone(), Arc::new(DictStore::from_dir(path).await.unwrap()))
+    }
+
+    fn resource(body: &[u8]) -> WarcRecord {
+        let mut record = WarcRecord::new("resource".to_string());
+        record.set_body(body.to_vec());
+        record
+    }
+
+    #[tokio::test]
+    async fn failed_append_does_not_undo_later_ones() {
+        let (path, dict_store) = empty_collection().await;
+        let coll = load_collection(&path, dict_store.clone()).await.unwrap();
+
+        /* the record is written but the index can't be */
+        fs::create_dir(format!("{}/index.cdx", path)).await.unwrap();
+        assert!(coll.add_warc(&resource(b"lost")).await.is_err());
+        assert_eq!(file_size(&format!("{}/records.1.warc", path)).await, 0);
+        fs::remove_dir(format!("{}/index.cdx", path)).await.unwrap();
+
+        let cdx = coll.add_warc(&resource(b"kept")).await.unwrap();
+        drop(coll);
+
+        let coll = load_collection(&path, dict_store).await.unwrap();
+        let mut reader = coll.iter_cdx().await.unwrap();
+        let stored = reader.async_next().await.unwrap().unwrap();
+        assert_eq!(stored.get_record_id(), cdx.get_record_id());
+        assert!(reader.async_next().await.unwrap().is_none());
+
+        let record = coll.get_record(&stored.get_file_name().unwrap(), stored.get_file_offset().unwrap()).await.unwrap().unwrap();
+        assert_eq!(record.get_body(), b"kept");
+
+        fs::remove_dir_all(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn recovers_interrupted_operations() {
+        let (path, dict_store) = empty_collection().await;
+        let coll = load_collection(&path, dict_store.clone()).await.unwrap();
+        let cdx = coll.add_warc(&resource(b"kept")).await.unwrap();
+        let records_path = format!("{}/records.1.warc", path);
+        let (records_size, index_size) = (file_size(&records_path).await, file_size(&format!("{}/index.cdx", path)).await);
+
+        /* crash in the middle of an append and of a deletion */
+        coll.journal.begin(JournalOp::Append{file: Some("records.1.warc".to_string()), file_size: records_size, index_size}).await.unwrap();
+        fs::OpenOptions::new().append(true).open(&records_path).await.unwrap()
+            .write_all(b"WARC/1.1\r\nWARC-Type: res").await.unwrap();
+        coll.journal.begin(JournalOp::SyncDb).await.unwrap();
+        drop(coll);
+
+        let coll = load_collection(&path, dict_store).await.unwrap();
+        assert_eq!(file_size(&records_path).await, records_size);
+        assert!(coll.needs_db_resync().await);
+
+        let mut reader = coll.iter_cdx().await.unwrap();
+        assert_eq!(reader.async_next().await.unwrap().unwrap().get_record_id(), cdx.get_record_id());
+        assert!(reader.async_next().await.unwrap().is_none());
+
+        fs::remove_dir_all(&path).await.unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use std::collections::HashSet;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, sync::Mutex};
+
+use super::collections::CollectionManifest;
+
+/// intent of a collection mutation, written before the mutation happens.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JournalOp {
+    /// records are appended to `file` (if any) and to the index.
+    /// rolled back by truncating both files to their previous size.
+    Append {
+        file: Option<String>,
+        file_size: u64,
+        index_size: u64
+    },
+    /// files are being written under a staged (dot-prefixed) name.
+    /// rolled back by removing staged files.
+    Stage,
+    /// staged files are complete and replace the current ones.
+    /// rolled forward by renaming, writing the manifest and removing obsolete files.
+    /// with `resync_db`, the entry ends once the database is updated,
+    /// so an interrupted switch flags the collection for a database resync.
+    Switch {
+        renames: Vec<(String, String)>,
+        removals: Vec<String>,
+        manifest: Option<CollectionManifest>,
+        #[serde(default)]
+        resync_db: bool
+    },
+    /// the database is being updated to match the files (e.g. a record deletion).
+    /// flags the collection for a database resync.
+    SyncDb,
+    /// the collection is being deleted, rolled forward.
+    Delete
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum JournalEntry {
+    Begin { begin: u64, op: JournalOp },
+    End { end: u64 }
+}
+
+struct JournalState {
+    fp: fs::File,
+    seq: u64,
+    pending: HashSet<u64>
+}
+
+pub struct Journal {
+    state: Mutex<JournalState>
+}
+
+impl Journal {
+    /// opens the journal and returns operations which never ended (in log order).
+    pub async fn open(path: &str) -> anyhow::Result<(Journal, Vec<JournalOp>)> {
+        let mut ops: Vec<(u64, JournalOp)> = Vec::new();
+        let mut seq: u64 = 0;
+
+        if let Ok(fp) = fs::File::open(path).await {
+            let mut lines = BufReader::new(fp).lines();
+            while let Some(line) = lines.next_line().await? {
+                match serde_json::from_str::<JournalEntry>(&line) {
+                    Ok(JournalEntry::Begin { begin, op }) => {
+                        seq = seq.max(begin);
+                        ops.push((begin, op));
+                    },
+                    Ok(JournalEntry::End { end }) => ops.retain(|(s, _)| *s != end),
+                    // a torn line can only be the last one, its operation never started.
+                    Err(x) => warn!("{}: ignoring invalid journal entry: {}", path, x)
+                }
+            }
+        }
+
+        let fp = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path).await?;
+
+        debug!("{}: {} pending operation(s)", path, ops.len());
+        Ok((
+            Journal{state: Mutex::new(JournalState{fp, seq, pending: HashSet::new()})},
+            ops.into_iter().map(|(_, op)| op).collect()))
+    }
+
+    pub async fn begin(&self, op: JournalOp) -> anyhow::Result<u64> {
+        let mut state = self.state.lock().await;
+        state.seq += 1;
+        let seq = state.seq;
+
+        Self::write_entry(&mut state.fp, &JournalEntry::Begin { begin: seq, op }).await?;
+        state.pending.insert(seq);
+        Ok(seq)
+    }
+
+    pub async fn end(&self, seq: u64) -> anyhow::Result<()> {
+        let mut state = self.state.lock().await;
+
+        Self::write_entry(&mut state.fp, &JournalEntry::End { end: seq }).await?;
+        state.pending.remove(&seq);
+
+        // nothing left to recover, so we don't let the journal grow forever.
+        if state.pending.is_empty() {
+            state.fp.set_len(0).await?;
+            state.fp.sync_data().await?;
+        }
+        Ok(())
+    }
+
+    /// forgets every operation, must be called once they are recovered.
+    pub async fn clear(&self) -> anyhow::Result<()> {
+        let mut state = self.state.lock().await;
+        state.pending.clear();
+        state.fp.set_len(0).await?;
+        state.fp.sync_data().await?;
+        Ok(())
+    }
+
+    async fn write_entry(fp: &mut fs::File, entry: &JournalEntry) -> anyhow::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        fp.write_all(line.as_bytes()).await?;
+        fp.sync_data().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn journal_path() -> String {
+        format!("{}/masstuffy-test-{}.journal", std::env::temp_dir().display(), uuid::Uuid::new_v4())
+    }
+
+    fn append(file_size: u64) -> JournalOp {
+        JournalOp::Append{file: None, file_size, index_size: 0}
+    }
+
+    #[tokio::test]
+    async fn returns_operations_which_never_ended() {
+        let path = journal_path();
+        let (journal, ops) = Journal::open(&path).await.unwrap();
+        assert!(ops.is_empty());
+
+        let first = journal.begin(append(1)).await.unwrap();
+        journal.begin(JournalOp::SyncDb).await.unwrap();
+        journal.begin(append(3)).await.unwrap();
+        journal.end(first).await.unwrap();
+        drop(journal);
+
+        let (journal, ops) = Journal::open(&path).await.unwrap();
+        assert!(matches!(ops[..], [JournalOp::SyncDb, JournalOp::Append{file_size: 3, ..}]));
+
+        /* once recovered */
+        journal.clear().await.unwrap();
+        drop(journal);
+        assert!(Journal::open(&path).await.unwrap().1.is_empty());
+
+        fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn empties_once_everything_ended() {
+        let path = journal_path();
+        let (journal, _) = Journal::open(&path).await.unwrap();
+        let first = journal.begin(append(1)).await.unwrap();
+        let second = journal.begin(append(2)).await.unwrap();
+        journal.end(second).await.unwrap();
+        assert!(fs::metadata(&path).await.unwrap().len() > 0);
+        journal.end(first).await.unwrap();
+        assert_eq!(fs::metadata(&path).await.unwrap().len(), 0);
+
+        fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn ignores_torn_entries() {
+        let path = journal_path();
+        let (journal, _) = Journal::open(&path).await.unwrap();
+        journal.begin(JournalOp::Stage).await.unwrap();
+        drop(journal);
+
+        let mut fp = fs::OpenOptions::new().append(true).open(&path).await.unwrap();
+        fp.write_all(br#"{"begin":2,"op":{"kind":"app"#).await.unwrap();
+        drop(fp);
+
+        let (_, ops) = Journal::open(&path).await.unwrap();
+        assert!(matches!(ops[..], [JournalOp::Stage]));
+
+        fs::remove_file(&path).await.unwrap();
+    }
+}
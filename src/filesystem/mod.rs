@@ -31,6 +31,8 @@ use crate::{config::Config, warc::WarcRecord};
 
 pub mod collections;
 mod dict_store;
+mod journal;
+pub mod verify;
 
 pub struct FileSystem {
     path: String,
@@ -102,7 +104,7 @@ impl FileSystem {
         self.collection_uuids.read().await.get(slug).is_some()
     }
 
-    pub async fn create_collection(&mut self, slug: String, dictionary: Option<(String, u32)>) -> anyhow::Result<bool> {
+    pub async fn create_collection(&mut self, slug: String, compression: Option<String>, dict_id: Option<u32>, dedup: bool) -> anyhow::Result<bool> {
         if self.has_collection_slug(&slug).await {
             return Ok(false);
         }
@@ -118,7 +120,7 @@ impl FileSystem {
         let coll = collections::create_collection(
             &format!("{}/data/repository/", self.path),
             &slug,
-            dictionary,
+            compression, dict_id, dedup,
             self.dictionary_store.clone()).await?;
         
         let slug = coll.get_slug().await;
@@ -161,10 +163,51 @@ impl FileSystem {
         }
     }
 
+    pub async fn delete_record(&self, coll_uuid: &str, record_id: &str, db: &DBManager) -> anyhow::Result<bool> {
+        let colls = self.collection_uuids.read().await;
+        let coll = colls.get(coll_uuid);
+
+        if let Some(coll) = coll {
+            coll.read().await.delete_record(record_id, Some(db)).await?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
     pub fn get_listen_addr(&self) -> String {
         self.config.listen_addr.clone()
     }
 
+    pub fn get_search_max_limit(&self) -> i64 {
+        self.config.search_max_limit
+    }
+
+    pub fn get_signed_url_max_lifetime(&self) -> i64 {
+        self.config.signed_url_max_lifetime
+    }
+
+    pub fn get_rate_limit_window(&self) -> i64 {
+        self.config.rate_limit_window.max(1)
+    }
+
+    /// requests and bytes allowed per window (0: unlimited).
+    pub fn get_rate_limits(&self, anonymous: bool) -> (u64, u64) {
+        if anonymous {
+            (self.config.anonymous_rate_limit_requests, self.config.anonymous_rate_limit_bytes)
+        } else {
+            (self.config.token_rate_limit_requests, self.config.token_rate_limit_bytes)
+        }
+    }
+
+    /// key of the token hashes and url signatures (see `permissions::hash_token`).
+    pub fn get_secret_key(&self) -> anyhow::Result<&str> {
+        match self.config.secret_key.as_deref() {
+            Some(key) if !key.is_empty() => Ok(key),
+            _ => Err(anyhow!("`secret_key` is not set in config.json"))
+        }
+    }
+
     pub async fn get_buffer_path(&self, name: &str, create: bool) -> anyhow::Result<(String, bool)>{
         let path = format!("{}/data/buffer/{}/", self.path, name); //TODO: validate no traversal path
 
@@ -216,6 +259,19 @@ impl FileSystem {
         Ok(())
     }
 
+    /// resyncs the database rows of collections flagged by an interrupted switch.
+    pub async fn resync_databases(&self, db: &DBManager) -> anyhow::Result<()> {
+        let colls: Vec<Arc<RwLock<Collection>>> = self.collection_uuids.read().await.values().cloned().collect();
+        for coll in colls {
+            let coll = coll.read().await;
+            if coll.needs_db_resync().await {
+                coll.resync_db(db).await?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn get_coll_uuid(&self, coll_slug: &str) -> anyhow::Result<String> {
         if let Some(col) = self.collection_slugs.read().await.get(coll_slug) {
             Ok(col.read().await.get_uuid().await)
@@ -239,10 +295,17 @@ impl FileSystem {
 
     pub fn get_default_permissions(&self) -> TokenInfo {
         TokenInfo {
-            token: "anonymous".to_string(),
+            id: "anonymous".to_string(),
+            token_hash: String::new(),
             comment: String::new(),
             read_perms: TokenPermission::from_fs_perms(&self.config.anonymous_read_perms_kind, &self.config.anonymous_read_perms),
             write_perms: TokenPermission::from_fs_perms(&self.config.anonymous_write_perms_kind, &self.config.anonymous_write_perms),
-            delete_perms: TokenPermission::from_fs_perms(&self.config.anonymous_delete_perms_kind, &self.config.anonymous_delete_perms)}
+            delete_perms: TokenPermission::from_fs_perms(&self.config.anonymous_delete_record_perms_kind, &self.config.anonymous_delete_record_perms),
+            admin: false,
+            create_coll_perms: TokenPermission::from_fs_perms(&self.config.anonymous_create_coll_perms_kind, &self.config.anonymous_create_coll_perms),
+            delete_coll_perms: TokenPermission::from_fs_perms(&self.config.anonymous_delete_coll_perms_kind, &self.config.anonymous_delete_coll_perms),
+            list_coll_perms: TokenPermission::from_fs_perms(&self.config.anonymous_list_coll_perms_kind, &self.config.anonymous_list_coll_perms),
+            expires_at: None,
+            last_used_at: None}
     }
 }
\ No newline at end of file
@@ -0,0 +1,256 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use log::info;
+use tokio::{fs, sync::RwLock};
+
+use crate::database::{structs::RECORD_FLAG_ACTIVE, DBManager};
+
+use super::{collections::Collection, FileSystem};
+
+const DB_PAGE_SIZE: i64 = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// index line without file, offset or size.
+    InvalidIndexEntry,
+    /// record file referenced by the index doesn't exist.
+    MissingFile,
+    /// record goes past the end of its file.
+    Truncated,
+    /// record doesn't fill exactly its indexed size.
+    SizeMismatch,
+    /// record can't be decompressed or parsed.
+    Unreadable,
+    /// record's WARC-Record-ID differs from the index.
+    IdMismatch,
+    /// record or row doesn't use the collection's dictionary.
+    DictionaryMismatch,
+    /// indexed record without database row.
+    MissingFromDatabase,
+    /// database row without indexed record (or unknown collection).
+    OrphanRow,
+    /// several database rows for the same record.
+    DuplicateRow,
+    /// database row active while the record is deleted or unreadable.
+    ActiveDeletedRecord,
+    /// database row inactive while the record is not deleted.
+    InactiveRecord,
+    /// record file not referenced by the index.
+    OrphanFile
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IssueKind::InvalidIndexEntry => "invalid_index_entry",
+            IssueKind::MissingFile => "missing_file",
+            IssueKind::Truncated => "truncated",
+            IssueKind::SizeMismatch => "size_mismatch",
+            IssueKind::Unreadable => "unreadable",
+            IssueKind::IdMismatch => "id_mismatch",
+            IssueKind::DictionaryMismatch => "dictionary_mismatch",
+            IssueKind::MissingFromDatabase => "missing_from_database",
+            IssueKind::OrphanRow => "orphan_row",
+            IssueKind::DuplicateRow => "duplicate_row",
+            IssueKind::ActiveDeletedRecord => "active_deleted_record",
+            IssueKind::InactiveRecord => "inactive_record",
+            IssueKind::OrphanFile => "orphan_file"
+        })
+    }
+}
+
+pub struct VerifyIssue {
+    pub collection: String,
+    pub kind: IssueKind,
+    pub detail: String,
+    pub repaired: bool
+}
+
+#[derive(PartialEq, Eq)]
+enum EntryState {
+    Live,
+    Deleted,
+    Broken
+}
+
+struct IndexEntry {
+    record_id: String,
+    state: EntryState,
+    has_row: bool
+}
+
+impl FileSystem {
+    /// cross-checks record files, indexes and database.
+    /// files are never modified, `repair` only makes the database match them.
+    pub async fn verify(&self, db: &DBManager, repair: bool) -> anyhow::Result<Vec<VerifyIssue>> {
+        let mut issues: Vec<VerifyIssue> = Vec::new();
+
+        let colls: Vec<Arc<RwLock<Collection>>> = self.collection_uuids.read().await.values().cloned().collect();
+        for coll in colls {
+            verify_collection(&*coll.read().await, db, repair, &mut issues).await?;
+        }
+
+        for uuid in db.get_collection_uuids().await? {
+            if !self.has_collection_uuid(&uuid).await {
+                /* a collection failing to load is not loaded either,
+                   its rows are only deleted once its directory is gone */
+                let exists = fs::metadata(format!("{}/data/repository/{}", self.path, uuid)).await.is_ok();
+                let repaired = repair && !exists;
+                if repaired {
+                    db.delete_collection(&uuid).await?;
+                }
+
+                issues.push(VerifyIssue{
+                    collection: uuid,
+                    kind: IssueKind::OrphanRow,
+                    detail: if exists {
+                        "rows of a collection which failed to load".to_string()
+                    } else {
+                        "rows of an unknown collection".to_string()
+                    },
+                    repaired});
+            }
+        }
+
+        Ok(issues)
+    }
+}
+
+async fn verify_collection(coll: &Collection, db: &DBManager, repair: bool, issues: &mut Vec<VerifyIssue>) -> anyhow::Result<()> {
+    let slug = coll.get_slug().await;
+    let uuid = coll.get_uuid().await;
+    let (dict_id, dict_algo) = coll.get_dict().await;
+    let dict_id = dict_id.map(|d| d as i64);
+
+    let mut report = |kind: IssueKind, detail: String, repaired: bool| {
+        issues.push(VerifyIssue{collection: slug.clone(), kind, detail, repaired});
+    };
+
+    info!("{}: checking records...", slug);
+    let tombstones = coll.get_tombstones().await?;
+    let mut entries: HashMap<(String, i64), IndexEntry> = HashMap::new();
+    let mut cdx_records = Vec::new();
+    let mut reader = coll.iter_cdx().await?;
+    while let Some(cdx) = reader.async_next().await? {
+        if cdx.is_tombstone() {
+            continue;
+        }
+
+        let mut state = if tombstones.contains(&cdx.get_record_id()) {
+            EntryState::Deleted
+        } else {
+            EntryState::Live
+        };
+
+        if let Some((kind, detail)) = coll.check_record(&cdx).await? {
+            report(kind, format!("{}: {}", cdx.get_record_id(), detail), false);
+            state = EntryState::Broken;
+        }
+
+        if let (Some(filename), Some(offset)) = (cdx.get_file_name(), cdx.get_file_offset()) {
+            entries.insert((filename, offset), IndexEntry{record_id: cdx.get_record_id(), state, has_row: false});
+            cdx_records.push(cdx);
+        }
+    }
+
+    info!("{}: checking database...", slug);
+    let mut last_id: i64 = 0;
+    loop {
+        let rows = db.get_collection_records(&uuid, last_id, DB_PAGE_SIZE).await?;
+        if rows.is_empty() {
+            break;
+        }
+
+        for row in &rows {
+            let is_active = (row.flags & RECORD_FLAG_ACTIVE) != 0;
+            let entry = entries.get_mut(&(row.filename.clone(), row.offset))
+                .filter(|e| e.record_id == row.identifier);
+
+            if entry.is_none() {
+                if repair {
+                    db.delete_record_row(row.id).await?;
+                }
+                report(IssueKind::OrphanRow, format!("{}: {}@{} is not indexed", row.identifier, row.filename, row.offset), repair);
+                continue;
+            }
+            let entry = entry.unwrap();
+
+            if entry.has_row {
+                if repair {
+                    db.delete_record_row(row.id).await?;
+                }
+                report(IssueKind::DuplicateRow, row.identifier.clone(), repair);
+                continue;
+            }
+
+            if row.dict_id != dict_id || row.dict_type != dict_algo {
+                // deleted so it gets reinserted with the right dictionary
+                if repair {
+                    db.delete_record_row(row.id).await?;
+                }
+                report(IssueKind::DictionaryMismatch, format!("{}: row uses dictionary {:?}", row.identifier, row.dict_id), repair);
+                continue;
+            }
+
+            entry.has_row = true;
+            if is_active && entry.state != EntryState::Live {
+                if repair {
+                    db.set_record_flags(row.id, row.flags & !RECORD_FLAG_ACTIVE).await?;
+                }
+                report(IssueKind::ActiveDeletedRecord, row.identifier.clone(), repair);
+            } else if !is_active && entry.state == EntryState::Live {
+                if repair {
+                    db.set_record_flags(row.id, row.flags | RECORD_FLAG_ACTIVE).await?;
+                }
+                report(IssueKind::InactiveRecord, row.identifier.clone(), repair);
+            }
+        }
+
+        last_id = rows.last().unwrap().id;
+    }
+
+    for cdx in &cdx_records {
+        let entry = &entries[&(cdx.get_file_name().unwrap(), cdx.get_file_offset().unwrap())];
+        if entry.has_row || entry.record_id != cdx.get_record_id() {
+            continue;
+        }
+
+        let flags = if entry.state == EntryState::Live {
+            RECORD_FLAG_ACTIVE
+        } else {
+            0
+        };
+
+        if repair {
+            db.insert_record(&uuid, cdx, flags, dict_id, dict_algo.as_deref()).await?;
+        }
+        report(IssueKind::MissingFromDatabase, cdx.get_record_id(), repair);
+    }
+
+    info!("{}: checking files...", slug);
+    for filename in coll.list_record_files().await? {
+        if !entries.keys().any(|(f, _)| *f == filename) {
+            report(IssueKind::OrphanFile, filename, false);
+        }
+    }
+
+    Ok(())
+}
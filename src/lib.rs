@@ -22,4 +22,5 @@ pub mod warc;
 pub mod database;
 pub mod constants;
 pub mod utils;
-pub mod permissions;
\ No newline at end of file
+pub mod permissions;
+pub mod signed_urls;
\ No newline at end of file
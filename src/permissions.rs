@@ -18,35 +18,80 @@
 
 use std::fmt::Display;
 use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use data_encoding::HEXLOWER;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use crate::{database::{structs::DBToken, DBManager}, filesystem::FileSystem};
 
+#[derive(Clone, Copy)]
 pub enum PermissionType {
     READ,
     WRITE,
-    DELETE
+    DELETE,
+    /// create collections
+    CREATE,
+    /// delete collections
+    DROP,
+    /// list collections
+    LIST
 }
 
+#[derive(Clone)]
 pub struct TokenInfo {
-    pub token: String,
+    /// public identifier, the token itself is only known by its holder
+    pub id: String,
+    /// see `hash_token`
+    pub token_hash: String,
     pub comment: String,
 
     pub read_perms: TokenPermission,
     pub write_perms: TokenPermission,
     pub delete_perms: TokenPermission,
+
+    /// can manage tokens
+    pub admin: bool,
+
+    /* checked against the slug of the collection */
+    pub create_coll_perms: TokenPermission,
+    pub delete_coll_perms: TokenPermission,
+    pub list_coll_perms: TokenPermission,
+
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
 }
 
 impl TokenInfo {
     pub fn from_db_row(token: DBToken) -> Self {
         Self{
-            token: token.token,
+            id: token.id,
+            token_hash: token.token,
             comment: token.comment,
             read_perms: TokenPermission::from_db_perms(token.read_perms_kind, token.read_perms),
             write_perms: TokenPermission::from_db_perms(token.write_perms_kind, token.write_perms),
-            delete_perms: TokenPermission::from_db_perms(token.delete_perms_kind, token.delete_perms)
+            delete_perms: TokenPermission::from_db_perms(token.delete_perms_kind, token.delete_perms),
+            admin: token.admin,
+            create_coll_perms: TokenPermission::from_db_perms(token.create_coll_perms_kind, token.create_coll_perms),
+            delete_coll_perms: TokenPermission::from_db_perms(token.delete_coll_perms_kind, token.delete_coll_perms),
+            list_coll_perms: TokenPermission::from_db_perms(token.list_coll_perms_kind, token.list_coll_perms),
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at
+        }
+    }
+
+    pub fn has_access(&self, permtype: PermissionType, coll_slug: &str) -> bool {
+        match permtype {
+            PermissionType::READ => self.read_perms.check(coll_slug),
+            PermissionType::WRITE => self.write_perms.check(coll_slug),
+            PermissionType::DELETE => self.delete_perms.check(coll_slug),
+            PermissionType::CREATE => self.create_coll_perms.check(coll_slug),
+            PermissionType::DROP => self.delete_coll_perms.check(coll_slug),
+            PermissionType::LIST => self.list_coll_perms.check(coll_slug)
         }
     }
 }
 
+#[derive(Clone)]
 pub enum TokenPermission {
     None,
     Any,
@@ -118,19 +163,49 @@ impl Display for TokenPermission {
     }
 }
 
+/// tokens are stored as an HMAC-SHA256 keyed with `secret_key`,
+/// so a dump of the database doesn't hold usable tokens.
+pub fn hash_token(secret_key: &str, token: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret_key.as_bytes())
+        .expect("hmac accepts keys of any size");
+    mac.update(token.as_bytes());
+    HEXLOWER.encode(&mac.finalize().into_bytes())
+}
+
+pub fn generate_token() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// finds the (unexpired) token and records its use.
+pub async fn lookup_token(db: &DBManager, fs: &FileSystem, token: &str) -> anyhow::Result<Option<TokenInfo>> {
+    if token.is_empty() {
+        return Ok(None);
+    }
+
+    let token_info = db.get_permissions(&hash_token(fs.get_secret_key()?, token)).await?;
+    if let Some(ti) = &token_info {
+        db.touch_token(&ti.id).await?;
+    }
+    Ok(token_info)
+}
+
 pub async fn check_access_token(db: &DBManager, fs: &FileSystem, permtype: PermissionType, token: &str, coll_slug: &str) -> anyhow::Result<bool> {
     let token_info = 
-    if let Some(ti) = db.get_permissions(token).await? {
+    if let Some(ti) = lookup_token(db, fs, token).await? {
         ti
     } else {
         fs.get_default_permissions()
     };
 
-    Ok(match permtype {
-        PermissionType::READ => token_info.read_perms.check(coll_slug),
-        PermissionType::WRITE => token_info.write_perms.check(coll_slug),
-        PermissionType::DELETE => token_info.delete_perms.check(coll_slug)
-    })
+    Ok(token_info.has_access(permtype, coll_slug))
+}
+
+pub async fn assert_admin(db: &DBManager, fs: &FileSystem, token: &str) -> anyhow::Result<()> {
+    if !lookup_token(db, fs, token).await?.is_some_and(|ti| ti.admin) {
+        Err(anyhow!("forbidden"))
+    } else {
+        Ok(())
+    }
 }
 
 pub async fn assert_access(db: &DBManager, fs: &FileSystem, permtype: PermissionType, token: &str, coll_slug: &str) -> anyhow::Result<()> {
@@ -139,4 +214,40 @@ pub async fn assert_access(db: &DBManager, fs: &FileSystem, permtype: Permission
     } else {
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_tokens_with_the_secret_key() {
+        let token = generate_token();
+        let hash = hash_token("secret", &token);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_token("secret", &token));
+        assert_ne!(hash, hash_token("other", &token));
+        assert_ne!(hash, hash_token("secret", &generate_token()));
+        assert!(!hash.contains(&token));
+    }
+
+    #[test]
+    fn checks_collection_slugs() {
+        let list = TokenPermission::from_fs_perms("list", &"news,blogs".to_string());
+        assert!(list.check("news") && list.check("blogs") && !list.check("new"));
+
+        let prefix = TokenPermission::from_db_perms(3, "crawl-".to_string());
+        assert!(prefix.check("crawl-2024") && !prefix.check("crawl"));
+
+        assert!(TokenPermission::from_fs_perms("any", &String::new()).check("news"));
+        assert!(!TokenPermission::from_fs_perms("unknown", &"news".to_string()).check("news"));
+    }
+
+    #[test]
+    fn round_trips_through_the_database_format() {
+        for perms in [TokenPermission::None, TokenPermission::Any,
+            TokenPermission::List(vec!["a".to_string(), "b".to_string()]), TokenPermission::Prefix("p".to_string())] {
+            let stored = TokenPermission::from_db_perms(perms.get_perms_kind(), perms.get_perms());
+            assert_eq!(stored.to_string(), perms.to_string());
+        }
+    }
+}
@@ -0,0 +1,205 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use anyhow::anyhow;
+use chrono::Utc;
+use data_encoding::HEXLOWER;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+pub const COLLECTION_PARAM: &str = "masstuffy_collection";
+pub const EXPIRES_PARAM: &str = "masstuffy_expires";
+pub const FLAGS_PARAM: &str = "masstuffy_flags";
+pub const SIGNATURE_PARAM: &str = "masstuffy_signature";
+
+/// signature parameters of a request.
+pub struct UrlSignature {
+    /// uuid of the collection the url may be served from
+    pub collection: String,
+    /// unix timestamp
+    pub expires: i64,
+    /// flags the url may be requested with
+    pub flags: String,
+    pub signature: String
+}
+
+impl UrlSignature {
+    /// checks the signature of `resource` (see `parse_record_path`) requested with `flags`,
+    /// the served records must belong to `collection`.
+    pub fn verify(&self, secret_key: &str, resource: &str, flags: &str) -> bool {
+        if self.expires <= Utc::now().timestamp() || !flags.chars().all(|c| self.flags.contains(c)) {
+            return false;
+        }
+
+        let Ok(signature) = HEXLOWER.decode(self.signature.as_bytes()) else {
+            return false;
+        };
+        mac(secret_key, resource, &self.collection, self.expires, &self.flags).verify_slice(&signature).is_ok()
+    }
+}
+
+fn mac(secret_key: &str, resource: &str, collection: &str, expires: i64, flags: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret_key.as_bytes())
+        .expect("hmac accepts keys of any size");
+    // prefixed so signatures can't be mistaken for token hashes
+    mac.update(format!("signed-url\n{}\n{}\n{}\n{}", resource, collection, expires, flags).as_bytes());
+    mac
+}
+
+/// splits `/id/:flags/:id` and `/url/:flags/:date/*url` into their flags
+/// and the record they point to (`/id/:id` or `/url/:date/*url`).
+pub fn parse_record_path(path: &str) -> Option<(String, String)> {
+    if let Some(rest) = path.strip_prefix("/id/") {
+        let (flags, id) = rest.split_once('/')?;
+        if id.is_empty() || id.contains('/') {
+            return None;
+        }
+        Some((flags.to_string(), format!("/id/{}", id)))
+    } else if let Some(rest) = path.strip_prefix("/url/") {
+        let (flags, rest) = rest.split_once('/')?;
+        let (date, url) = rest.split_once('/')?;
+        if date.is_empty() || url.is_empty() {
+            return None;
+        }
+        Some((flags.to_string(), format!("/url/{}/{}", date, url)))
+    } else {
+        None
+    }
+}
+
+/// appends signature parameters to a record path, `flags` defaults to the flags of the path.
+/// `collection` is the uuid of the record's collection.
+pub fn sign_path(secret_key: &str, path: &str, collection: &str, expires: i64, flags: Option<&str>) -> anyhow::Result<String> {
+    let Some((path_flags, resource)) = parse_record_path(path) else {
+        return Err(anyhow!("not a record path: {}", path));
+    };
+
+    let flags = flags.unwrap_or(&path_flags);
+    if !flags.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(anyhow!("invalid flags: {}", flags));
+    }
+    if !path_flags.chars().all(|c| flags.contains(c)) {
+        return Err(anyhow!("the path's flags ({}) are not all allowed ({})", path_flags, flags));
+    }
+
+    if !collection.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        return Err(anyhow!("invalid collection uuid: {}", collection));
+    }
+
+    let signature = HEXLOWER.encode(&mac(secret_key, &resource, collection, expires, flags).finalize().into_bytes());
+    Ok(format!("{}{}{}={}&{}={}&{}={}&{}={}",
+        path, if path.contains('?') { '&' } else { '?' },
+        COLLECTION_PARAM, collection,
+        EXPIRES_PARAM, expires,
+        FLAGS_PARAM, flags,
+        SIGNATURE_PARAM, signature))
+}
+
+/// removes the signature parameters from a query string,
+/// returns what is left of it and the signature (when all of its parameters are there).
+pub fn strip_signature(query: &str) -> (String, Option<UrlSignature>) {
+    let mut rest = Vec::new();
+    let (mut collection, mut expires, mut flags, mut signature) = (None, None, None, None);
+
+    for param in query.split('&') {
+        match param.split_once('=') {
+            Some((COLLECTION_PARAM, v)) => collection = Some(v.to_string()),
+            Some((EXPIRES_PARAM, v)) => expires = v.parse::<i64>().ok(),
+            Some((FLAGS_PARAM, v)) => flags = Some(v.to_string()),
+            Some((SIGNATURE_PARAM, v)) => signature = Some(v.to_string()),
+            _ => rest.push(param)
+        }
+    }
+
+    let signature = match (collection, expires, flags, signature) {
+        (Some(collection), Some(expires), Some(flags), Some(signature)) => Some(UrlSignature{collection, expires, flags, signature}),
+        _ => None
+    };
+    (rest.join("&"), signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "secret";
+    const COLLECTION: &str = "983b9eaf-066f-4d5e-93e4-01c490754933";
+
+    /// signs `path` and parses the signature back as a request would.
+    fn signed(path: &str, expires: i64, flags: Option<&str>) -> (String, UrlSignature) {
+        let url = sign_path(KEY, path, COLLECTION, expires, flags).unwrap();
+        let (path, query) = url.split_once('?').unwrap();
+        let (rest, signature) = strip_signature(query);
+        assert!(rest.is_empty());
+        (path.to_string(), signature.unwrap())
+    }
+
+    fn in_an_hour() -> i64 {
+        Utc::now().timestamp() + 3600
+    }
+
+    #[test]
+    fn parses_record_paths() {
+        assert_eq!(parse_record_path("/id/pd/urn:uuid:1"), Some(("pd".to_string(), "/id/urn:uuid:1".to_string())));
+        assert_eq!(parse_record_path("/url/-/2020/http://example.com/a"),
+            Some(("-".to_string(), "/url/2020/http://example.com/a".to_string())));
+        assert_eq!(parse_record_path("/id/p/a/b"), None);
+        assert_eq!(parse_record_path("/search"), None);
+    }
+
+    #[test]
+    fn verifies_signatures() {
+        let (_, signature) = signed("/id/p/urn:uuid:1", in_an_hour(), Some("pd"));
+        assert_eq!(signature.collection, COLLECTION);
+        assert!(signature.verify(KEY, "/id/urn:uuid:1", "p"));
+        assert!(signature.verify(KEY, "/id/urn:uuid:1", "pd"));
+
+        assert!(!signature.verify("other", "/id/urn:uuid:1", "p"));
+        assert!(!signature.verify(KEY, "/id/urn:uuid:2", "p"));
+        assert!(!signature.verify(KEY, "/id/urn:uuid:1", "pw"));
+    }
+
+    #[test]
+    fn rejects_expired_signatures() {
+        let (_, signature) = signed("/id/p/urn:uuid:1", Utc::now().timestamp() - 1, None);
+        assert!(!signature.verify(KEY, "/id/urn:uuid:1", "p"));
+
+        let (_, mut signature) = signed("/id/p/urn:uuid:1", in_an_hour(), None);
+        signature.expires += 3600;
+        assert!(!signature.verify(KEY, "/id/urn:uuid:1", "p"));
+    }
+
+    #[test]
+    fn binds_signatures_to_their_collection() {
+        let (_, mut signature) = signed("/id/p/urn:uuid:1", in_an_hour(), None);
+        signature.collection = "b023035f-d79c-4245-aed0-37cec33f61c9".to_string();
+        assert!(!signature.verify(KEY, "/id/urn:uuid:1", "p"));
+
+        assert!(sign_path(KEY, "/id/p/urn:uuid:1", "../other", in_an_hour(), None).is_err());
+    }
+
+    #[test]
+    fn requires_the_path_flags() {
+        assert!(sign_path(KEY, "/id/pd/urn:uuid:1", COLLECTION, in_an_hour(), Some("p")).is_err());
+        assert!(sign_path(KEY, "/id/p/urn:uuid:1", COLLECTION, in_an_hour(), Some("p&x=1")).is_err());
+
+        let (path, signature) = signed("/id/p/urn:uuid:1", in_an_hour(), None);
+        assert_eq!(path, "/id/p/urn:uuid:1");
+        assert_eq!(signature.flags, "p");
+    }
+}
@@ -0,0 +1,77 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use anyhow::{bail, Context};
+use chrono::{Months, NaiveDateTime, TimeDelta};
+
+use crate::constants::MASSTUFFY_DATE_FMT;
+
+/// parses a `YYYY[mm[dd[HH[MM[SS]]]]]` date as the first second of the period it covers,
+/// or its last one when `end` is set (`2023` -> `20231231235959`).
+pub fn parse_date_prefix(value: &str, end: bool) -> anyhow::Result<NaiveDateTime> {
+    const PADDING: &str = "00000101000000";
+
+    if !matches!(value.len(), 4 | 6 | 8 | 10 | 12 | 14) || !value.bytes().all(|c| c.is_ascii_digit()) {
+        bail!("invalid date: {} (expected YYYY[mm[dd[HH[MM[SS]]]]])", value);
+    }
+
+    let start = NaiveDateTime::parse_from_str(
+        &format!("{}{}", value, &PADDING[value.len()..]), MASSTUFFY_DATE_FMT)
+        .with_context(|| format!("invalid date: {}", value))?;
+    if !end {
+        return Ok(start);
+    }
+
+    let next = match value.len() {
+        4 => start.checked_add_months(Months::new(12)),
+        6 => start.checked_add_months(Months::new(1)),
+        8 => start.checked_add_signed(TimeDelta::days(1)),
+        10 => start.checked_add_signed(TimeDelta::hours(1)),
+        12 => start.checked_add_signed(TimeDelta::minutes(1)),
+        _ => start.checked_add_signed(TimeDelta::seconds(1))
+    }.with_context(|| format!("date out of range: {}", value))?;
+
+    Ok(next - TimeDelta::seconds(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str, end: bool) -> String {
+        parse_date_prefix(value, end).unwrap().format(MASSTUFFY_DATE_FMT).to_string()
+    }
+
+    #[test]
+    fn covers_whole_periods() {
+        assert_eq!(parse("2023", false), "20230101000000");
+        assert_eq!(parse("2023", true), "20231231235959");
+        assert_eq!(parse("202402", true), "20240229235959");
+        assert_eq!(parse("20231231", true), "20231231235959");
+        assert_eq!(parse("2023123123", true), "20231231235959");
+        assert_eq!(parse("202312312359", false), "20231231235900");
+        assert_eq!(parse("20231231235959", true), "20231231235959");
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        for value in ["", "202", "20231", "2023-01", "20231301", "20230230", "2023010125"] {
+            assert!(parse_date_prefix(value, false).is_err(), "{}", value);
+        }
+    }
+}
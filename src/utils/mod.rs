@@ -21,12 +21,15 @@ use tokio::io::{AsyncRead, BufReader};
 use anyhow::Result;
 
 pub mod seek;
+pub mod counting;
+pub mod dates;
 
 pub async fn open_compressed(path: &str) -> Result<BufReader<Box<dyn AsyncRead + Unpin + Send>>> {
     let fp = tokio::fs::File::open(path).await?;
 
     if path.ends_with(".gz") {
-        let dec = GzipDecoder::new(BufReader::new(fp));
+        let mut dec = GzipDecoder::new(BufReader::new(fp));
+        dec.multiple_members(true); // .warc.gz holds a member per record
         return Ok(BufReader::new(Box::new(dec)));
     } else if path.ends_with(".zst") {
         let dec = ZstdDecoder::new(BufReader::new(fp));
@@ -88,6 +88,7 @@ impl FileManager {
         let mut files = self.files.write().await;
 
         files.remove(file_path);
+        self.filesizes.write().await.remove(file_path);
     }
 
     pub async fn append(&self, file_path: &str, buf: &[u8]) -> anyhow::Result<u64> {
@@ -112,6 +113,12 @@ impl FileManager {
         Ok(ret)
     }
 
+    pub async fn sync(&self, file_path: &str) -> anyhow::Result<()> {
+        let file = self.get_file(file_path).await?;
+        file.lock().await.get_ref().sync_data().await?;
+        Ok(())
+    }
+
     pub async fn get_file_size(&self, file_path: String) -> Option<u64> {
         let filesize = self.filesizes.read().await.get(&file_path).cloned();
 
@@ -0,0 +1,200 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use anyhow::{bail, Context};
+use chrono::NaiveDateTime;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+
+use crate::utils::{counting::CountingReader, open_compressed};
+
+use super::WarcRecord;
+
+/// url-record-v1 fields, v2 lines have more fields but still end with the length.
+struct ArcHeader {
+    url: String,
+    ip: String,
+    date: String,
+    content_type: String,
+    length: usize
+}
+
+impl ArcHeader {
+    fn from_line(line: &str) -> anyhow::Result<Self> {
+        let parts: Vec<&str> = line.split(' ').collect();
+        if parts.len() < 5 {
+            bail!("expected at least 5 fields in arc header but found {}", parts.len());
+        }
+
+        Ok(ArcHeader{
+            url: parts[0].to_string(),
+            ip: parts[1].to_string(),
+            date: parts[2].to_string(),
+            content_type: parts[3].to_string(),
+            length: match parts[parts.len()-1].parse() {
+                Ok(l) => l,
+                Err(_) => bail!("invalid arc record length '{}'", parts[parts.len()-1])
+            }
+        })
+    }
+
+    /// ARC dates are `YYYYmmddHHMMSS`, sometimes truncated (to the first day of the month or year).
+    fn warc_date(&self) -> anyhow::Result<String> {
+        let digits = format!("{}{}", self.date, &"00000101000000"[self.date.len().min(14)..]);
+        match NaiveDateTime::parse_from_str(&digits, "%Y%m%d%H%M%S") {
+            Ok(d) => Ok(d.format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+            Err(_) => bail!("invalid arc date '{}'", self.date)
+        }
+    }
+}
+
+/// reads ARC (v1) files and converts their records to WARC records,
+/// the file header is returned first as a `metadata` record.
+pub struct ArcReader {
+    br: CountingReader<BufReader<Box<dyn AsyncRead + Unpin + Send>>>,
+    header_read: bool
+}
+
+impl ArcReader {
+    pub async fn from_file(path: &str) -> anyhow::Result<ArcReader> {
+        Ok(ArcReader{
+            br: CountingReader::new(open_compressed(path).await?),
+            header_read: false
+        })
+    }
+
+    pub async fn async_next(&mut self) -> anyhow::Result<Option<WarcRecord>> {
+        let offset = self.br.position();
+        self.read_next().await.with_context(|| format!("arc record at offset {}", offset))
+    }
+
+    async fn read_next(&mut self) -> anyhow::Result<Option<WarcRecord>> {
+        /* records are separated by a newline, some writers add more */
+        let line = loop {
+            let mut line = String::new();
+            if self.br.read_line(&mut line).await? == 0 {
+                return Ok(None);
+            }
+
+            let line = line.trim_end_matches(['\r', '\n']);
+            if !line.is_empty() {
+                break line.to_string();
+            }
+        };
+
+        let header = ArcHeader::from_line(&line)?;
+        let mut content = vec![0; header.length];
+        self.br.read_exact(&mut content).await?;
+
+        if !self.header_read {
+            self.header_read = true;
+            if !header.url.starts_with("filedesc:") {
+                bail!("arc file doesn't start with a filedesc record");
+            }
+
+            let mut record = WarcRecord::new("metadata".to_string());
+            record.set_header("WARC-Target-URI".to_string(), header.url.clone());
+            record.set_header("WARC-Date".to_string(), header.warc_date()?);
+            record.set_header("Content-Type".to_string(), "application/x-arc-header".to_string());
+
+            let mut body = format!("{}\n", line).into_bytes();
+            body.extend(content);
+            record.set_body(body);
+            return Ok(Some(record));
+        }
+
+        let is_http = (header.url.starts_with("http:") || header.url.starts_with("https:"))
+            && content.starts_with(b"HTTP/");
+
+        let mut record = WarcRecord::new((if is_http {"response"} else {"resource"}).to_string());
+        record.set_header("WARC-Target-URI".to_string(), header.url.clone());
+        record.set_header("WARC-Date".to_string(), header.warc_date()?);
+        if header.ip != "-" && header.ip != "0.0.0.0" {
+            record.set_header("WARC-IP-Address".to_string(), header.ip.clone());
+        }
+        record.set_header("Content-Type".to_string(),
+            if is_http {"application/http; msgtype=response".to_string()} else {header.content_type.clone()});
+        record.set_body(content);
+
+        Ok(Some(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arc_record(fields: &str, content: &str) -> String {
+        format!("{} {}\n{}\n", fields, content.len(), content)
+    }
+
+    fn filedesc() -> String {
+        arc_record("filedesc://test.arc 0.0.0.0 20010203040506 text/plain",
+            "1 0 Test\nURL IP-address Archive-date Content-type Archive-length\n")
+    }
+
+    async fn reader(data: &str) -> ArcReader {
+        let path = format!("{}/masstuffy-test-{}.arc", std::env::temp_dir().display(), uuid::Uuid::new_v4());
+        tokio::fs::write(&path, data).await.unwrap();
+        let reader = ArcReader::from_file(&path).await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+        reader
+    }
+
+    #[tokio::test]
+    async fn converts_records() {
+        let data = format!("{}{}{}",
+            filedesc(),
+            arc_record("http://example.com/ 93.184.216.34 20010203040507 text/html", "HTTP/1.0 200 OK\r\nServer: x\r\n\r\nhello"),
+            arc_record("dns:example.com 0.0.0.0 2001 text/dns", "ok"));
+        let mut reader = reader(&data).await;
+
+        let header = reader.async_next().await.unwrap().unwrap();
+        assert_eq!(header.get_type().unwrap(), "metadata");
+        assert_eq!(header.get_target_uri().unwrap(), "filedesc://test.arc");
+
+        let response = reader.async_next().await.unwrap().unwrap();
+        assert_eq!(response.get_type().unwrap(), "response");
+        assert_eq!(response.get_date().unwrap().format("%Y%m%d%H%M%S").to_string(), "20010203040507");
+        assert_eq!(response.get_header("WARC-IP-Address").unwrap(), "93.184.216.34");
+        assert!(response.get_body().ends_with(b"hello"));
+
+        /* truncated dates are padded */
+        let resource = reader.async_next().await.unwrap().unwrap();
+        assert_eq!(resource.get_type().unwrap(), "resource");
+        assert_eq!(resource.get_date().unwrap().format("%Y%m%d%H%M%S").to_string(), "20010101000000");
+        assert!(resource.get_header("WARC-IP-Address").is_none());
+        assert_eq!(resource.get_body(), b"ok");
+
+        assert!(reader.async_next().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn reports_truncated_records() {
+        let data = format!("{}http://example.com/ - 20010203040507 text/html 100\nshort", filedesc());
+        let mut reader = reader(&data).await;
+        reader.async_next().await.unwrap();
+        let err = reader.async_next().await.unwrap_err();
+        assert!(err.to_string().starts_with("arc record at offset"));
+    }
+
+    #[tokio::test]
+    async fn requires_filedesc() {
+        let mut reader = reader(&arc_record("http://example.com/ - 20010203040507 text/html", "ok")).await;
+        assert!(reader.async_next().await.is_err());
+    }
+}
@@ -16,15 +16,22 @@
  *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
 **/
 
-use std::fmt;
+use std::{collections::VecDeque, fmt};
 
-use anyhow::bail;
-use log::{debug, error};
+use anyhow::{bail, Context};
+use chrono::Utc;
+use log::debug;
 use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 
-use crate::utils::open_compressed;
+use crate::{constants::MASSTUFFY_DATE_FMT, utils::open_compressed};
 
-use super::WarcRecord;
+use super::{payload::PayloadInfo, WarcRecord};
+
+/// record type used by index lines that mark a record as deleted.
+pub const CDX_TOMBSTONE_TYPE: &str = "tombstone";
+
+/// first line of sealed index segments, skipped by readers.
+pub const CDX_HEADER: &str = " CDX url type id date file offset size status mime payload_length payload_digest";
 
 #[derive(Clone)]
 pub struct CDXRecord {
@@ -34,7 +41,11 @@ pub struct CDXRecord {
     date: String,
     file_name: Option<String>,
     file_offset: Option<String>,
-    raw_size: Option<u64>
+    raw_size: Option<u64>,
+    status: Option<u16>,
+    mime: Option<String>,
+    payload_length: Option<u64>,
+    payload_digest: Option<String>
 }
 
 fn part2option(part: &str) -> Option<String> {
@@ -47,24 +58,54 @@ fn part2option(part: &str) -> Option<String> {
 
 impl CDXRecord {
     pub fn from_warc(warc: &WarcRecord) -> anyhow::Result<Self> {
+        let record_type = warc.get_type()?;
+        let payload = PayloadInfo::from_warc(warc, &record_type);
+
         Ok(CDXRecord{
             url: warc.get_target_uri(),
-            record_type: warc.get_type()?,
+            record_type,
             record_id: warc.get_record_id()?,
             date: warc.get_date()?.format("%Y%m%d%H%M%S").to_string(),
             file_name: None,
             file_offset: None,
-            raw_size: None
+            raw_size: None,
+            status: payload.status,
+            mime: payload.mime,
+            payload_length: payload.length,
+            payload_digest: payload.digest
         })
     }
 
+    /// creates an index line telling readers that `record_id` has been deleted.
+    pub fn tombstone(record_id: &str) -> Self {
+        CDXRecord{
+            url: None,
+            record_type: CDX_TOMBSTONE_TYPE.to_string(),
+            record_id: record_id.to_string(),
+            date: Utc::now().format(MASSTUFFY_DATE_FMT).to_string(),
+            file_name: None,
+            file_offset: None,
+            raw_size: None,
+            status: None,
+            mime: None,
+            payload_length: None,
+            payload_digest: None
+        }
+    }
+
+    pub fn is_tombstone(&self) -> bool {
+        self.record_type == CDX_TOMBSTONE_TYPE
+    }
+
+    /// reads 11-field lines as well as 7-field ones (written before payload fields existed).
     pub fn from_line(line: &str) -> anyhow::Result<Self> {
-        let parts: Vec<&str> = line.split(' ').collect();
+        let parts: Vec<&str> = line.trim_end_matches(['\r', '\n']).split(' ').collect();
 
-        if parts.len() != 7 && (parts.len() != 7 || parts[6] != "\n"){
-            bail!("expected 7 parts but found {}", parts.len());
+        if parts.len() != 7 && parts.len() != 11 {
+            bail!("expected 7 or 11 parts but found {}", parts.len());
         }
 
+        let payload_part = |i: usize| parts.get(i).and_then(|p| part2option(p));
         Ok(CDXRecord{
             url: part2option(parts[0]),
             record_type: parts[1].to_string(),
@@ -72,7 +113,12 @@ impl CDXRecord {
             date: parts[3].to_string(),
             file_name: part2option(parts[4]),
             file_offset: part2option(parts[5]),
-            raw_size: part2option(parts[6].trim()).map(|p| p.parse::<u64>().expect("invald raw_size"))
+            raw_size: part2option(parts[6]).map(|p| p.parse::<u64>()).transpose()
+                .with_context(|| format!("invalid raw size {:?}", parts[6]))?,
+            status: payload_part(7).and_then(|p| p.parse::<u16>().ok()),
+            mime: payload_part(8),
+            payload_length: payload_part(9).and_then(|p| p.parse::<u64>().ok()),
+            payload_digest: payload_part(10)
         })
     }
 
@@ -92,6 +138,10 @@ impl CDXRecord {
     pub fn get_record_type(&self) -> String {self.record_type.clone()}
     pub fn get_url(&self) -> Option<String> {self.url.clone()}
     pub fn get_file_name(&self) -> Option<String> {self.file_name.clone()}
+    pub fn get_status(&self) -> Option<u16> {self.status}
+    pub fn get_mime(&self) -> Option<String> {self.mime.clone()}
+    pub fn get_payload_length(&self) -> Option<u64> {self.payload_length}
+    pub fn get_payload_digest(&self) -> Option<String> {self.payload_digest.clone()}
     pub fn get_file_offset(&self) -> Option<i64> {
         if let Some(x) = &self.file_offset {
             if let Ok(b) = x.parse::<i64>() {
@@ -108,48 +158,104 @@ impl CDXRecord {
 impl fmt::Display for CDXRecord {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f,
-            "{} {} {} {} {} {} {}",
+            "{} {} {} {} {} {} {} {} {} {} {}",
             self.url.clone().unwrap_or("-".to_string()), self.record_type,
             self.record_id, self.date,
             self.file_name.clone().unwrap_or("-".to_string()),
             self.file_offset.clone().unwrap_or("-".to_string()),
-            self.raw_size.map(|r| format!("{}", r)).as_deref().unwrap_or("-")
+            self.raw_size.map(|r| format!("{}", r)).as_deref().unwrap_or("-"),
+            self.status.map(|s| format!("{}", s)).as_deref().unwrap_or("-"),
+            self.mime.as_deref().unwrap_or("-"),
+            self.payload_length.map(|l| format!("{}", l)).as_deref().unwrap_or("-"),
+            self.payload_digest.as_deref().unwrap_or("-")
         )?;
         Ok(())
     }
 }
 
 pub struct CDXFileReader {
-    br: BufReader<Box<dyn AsyncRead + Unpin + Send>>,
+    br: Option<BufReader<Box<dyn AsyncRead + Unpin + Send>>>,
+    /// file being read, for errors
+    path: String,
+    pending: VecDeque<String>,
     buff: String
 }
 
 impl CDXFileReader {
     pub async fn open(path: &str) -> anyhow::Result<Self> {
+        Self::open_all(vec![path.to_string()]).await
+    }
+
+    /// reads the files one after the other, as if they were a single index.
+    pub async fn open_all(paths: Vec<String>) -> anyhow::Result<Self> {
+        let mut pending: VecDeque<String> = paths.into();
+        let (br, path) = match pending.pop_front() {
+            Some(path) => (Some(open_compressed(&path).await
+                .with_context(|| format!("failed to open cdx file {}", path))?), path),
+            None => (None, String::new())
+        };
+
         Ok(CDXFileReader{
-            br: open_compressed(path).await?,
+            br, path, pending,
             buff: String::new()
         })
     }
 
-    pub async fn async_next(&mut self) -> Option<CDXRecord> {
-        self.buff.clear();
-        if let Err(x) = self.br.read_line(&mut self.buff).await {
-            error!("failed to read cdx line: {}", x);
-            return None
-        }
+    /// next entry, unreadable files and entries are errors so a truncated index is never taken as complete.
+    pub async fn async_next(&mut self) -> anyhow::Result<Option<CDXRecord>> {
+        loop {
+            let Some(br) = self.br.as_mut() else {
+                return Ok(None);
+            };
 
-        if self.buff.is_empty() {
-            debug!("cdx file ended");
-            return None
-        }
+            self.buff.clear();
+            br.read_line(&mut self.buff).await
+                .with_context(|| format!("failed to read cdx file {}", self.path))?;
 
-        let ret = CDXRecord::from_line(&self.buff);
-        if let Ok(x) = ret{
-            Some(x)
-        } else {
-            error!("failed to read cdx entry: {}", ret.err().unwrap());
-            None
+            if self.buff.is_empty() {
+                let Some(path) = self.pending.pop_front() else {
+                    debug!("cdx file ended");
+                    self.br = None;
+                    return Ok(None)
+                };
+
+                self.br = Some(open_compressed(&path).await
+                    .with_context(|| format!("failed to open cdx file {}", path))?);
+                self.path = path;
+                continue;
+            }
+
+            if self.buff.starts_with(" CDX") || self.buff.trim().is_empty() {
+                continue;
+            }
+
+            return CDXRecord::from_line(&self.buff)
+                .map(Some)
+                .with_context(|| format!("failed to read cdx entry of {}: {:?}", self.path, self.buff.trim_end()));
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_both_line_formats() {
+        let old = CDXRecord::from_line("http://example.com/ response urn:uuid:1 20250101000000 records.1.warc 0 120\n").unwrap();
+        assert_eq!(old.get_raw_size(), Some(120));
+        assert_eq!(old.get_status(), None);
+
+        let line = "http://example.com/ response urn:uuid:1 20250101000000 records.1.warc 0 120 200 text/html 5 sha1:ABC";
+        let new = CDXRecord::from_line(line).unwrap();
+        assert_eq!(new.get_file_offset(), Some(0));
+        assert_eq!(new.get_mime().as_deref(), Some("text/html"));
+        assert_eq!(new.to_string(), line);
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        assert!(CDXRecord::from_line("http://example.com/ response urn:uuid:1").is_err());
+        assert!(CDXRecord::from_line("http://example.com/ response urn:uuid:1 20250101000000 records.1.warc 0 big").is_err());
+    }
+}
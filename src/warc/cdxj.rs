@@ -0,0 +1,146 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use serde::Serialize;
+
+use super::massaged_url::massage_url;
+
+/// legend line of CDX11 files.
+pub const CDX11_HEADER: &str = " CDX N b a m s k r M S V g";
+
+/// Sort-friendly URI Reordering Transform of `url`,
+/// the key used by wayback tools to sort and lookup indexes.
+pub fn surt(url: &str) -> anyhow::Result<String> {
+    let massaged = massage_url(url)?.to_lowercase();
+    let Some((host, path)) = massaged.split_once(')') else {
+        return Ok(massaged);
+    };
+
+    let (domain, port) = match host.split_once(':') {
+        Some((d, p)) => (d, format!(":{}", p)),
+        None => (host, String::new())
+    };
+
+    Ok(format!("{}{}){}", domain.strip_suffix(",www").unwrap_or(domain), port, path))
+}
+
+#[derive(Serialize)]
+pub struct CDXJBlock {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    pub length: u64,
+    pub offset: u64,
+    pub filename: String
+}
+
+pub struct CDXJEntry {
+    pub surt: String,
+    pub timestamp: String,
+    pub block: CDXJBlock
+}
+
+impl CDXJEntry {
+    pub fn new(
+        url: &str, timestamp: &str,
+        filename: String, offset: u64, length: u64) -> anyhow::Result<Self> {
+        Ok(CDXJEntry{
+            surt: surt(url)?,
+            timestamp: timestamp.to_string(),
+            block: CDXJBlock{
+                url: url.to_string(),
+                mime: None, status: None, digest: None,
+                length, offset, filename
+            }
+        })
+    }
+
+    /// fills mime, status and digest from the values stored in the index.
+    pub fn with_details(mut self, record_type: &str, mime: Option<String>, status: Option<u16>, digest: Option<String>) -> Self {
+        self.block.mime = if record_type == "revisit" {Some("warc/revisit".to_string())} else {mime};
+        self.block.status = status.map(|s| s.to_string());
+        self.block.digest = digest.map(|d| d.strip_prefix("sha1:").unwrap_or(&d).to_string());
+        self
+    }
+
+    pub fn to_cdxj(&self) -> String {
+        format!("{} {} {}",
+            self.surt, self.timestamp,
+            serde_json::to_string(&self.block).unwrap())
+    }
+
+    pub fn to_cdx11(&self) -> String {
+        format!("{} {} {} {} {} {} - - {} {} {}",
+            self.surt, self.timestamp, self.block.url,
+            self.block.mime.as_deref().unwrap_or("-"),
+            self.block.status.as_deref().unwrap_or("-"),
+            self.block.digest.as_deref().unwrap_or("-"),
+            self.block.length, self.block.offset, self.block.filename)
+    }
+}
+
+/// sorts entries the way wayback tools expect them.
+pub fn sort_entries(entries: &mut [CDXJEntry]) {
+    entries.sort_by(|a, b| (&a.surt, &a.timestamp).cmp(&(&b.surt, &b.timestamp)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(url: &str, timestamp: &str) -> CDXJEntry {
+        CDXJEntry::new(url, timestamp, "records.1.warc".to_string(), 0, 10).unwrap()
+    }
+
+    #[test]
+    fn builds_surt_keys() {
+        assert_eq!(surt("http://www.Example.com/Path?b=1&a=2").unwrap(), "com,example)/path?a=2&b=1");
+        assert_eq!(surt("https://example.com:8080/").unwrap(), "com,example:8080)/");
+        assert_eq!(surt("http://sub.example.co.uk").unwrap(), "uk,co,example,sub)/");
+    }
+
+    #[test]
+    fn sorts_by_surt_then_date() {
+        let mut entries = vec![
+            entry("http://example.org/", "20200101000000"),
+            entry("http://www.example.com/b", "20200101000000"),
+            entry("http://example.com/b", "20190101000000"),
+            entry("http://example.com/a", "20210101000000")
+        ];
+        sort_entries(&mut entries);
+        let keys: Vec<(&str, &str)> = entries.iter().map(|e| (e.surt.as_str(), e.timestamp.as_str())).collect();
+        assert_eq!(keys, [
+            ("com,example)/a", "20210101000000"),
+            ("com,example)/b", "20190101000000"),
+            ("com,example)/b", "20200101000000"),
+            ("org,example)/", "20200101000000")
+        ]);
+    }
+
+    #[test]
+    fn formats_lines() {
+        let entry = entry("http://example.com/", "20200101000000")
+            .with_details("revisit", Some("text/html".to_string()), Some(200), Some("sha1:ABC".to_string()));
+        assert_eq!(entry.to_cdxj(), r#"com,example)/ 20200101000000 {"url":"http://example.com/","mime":"warc/revisit","status":"200","digest":"ABC","length":10,"offset":0,"filename":"records.1.warc"}"#);
+        assert_eq!(entry.to_cdx11(), "com,example)/ 20200101000000 http://example.com/ warc/revisit 200 ABC - - 10 0 records.1.warc");
+    }
+}
@@ -0,0 +1,234 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use anyhow::{bail, Context};
+use async_compression::tokio::bufread::{BrotliDecoder, DeflateDecoder, GzipDecoder, ZlibDecoder};
+use tokio::io::AsyncReadExt;
+
+use super::WarcRecord;
+
+/// headers that only make sense between the client and the original server.
+const UNSAFE_HEADERS: [&str; 16] = [
+    "connection", "keep-alive", "proxy-authenticate", "proxy-authorization",
+    "te", "trailer", "transfer-encoding", "upgrade", "content-length",
+    "set-cookie", "set-cookie2", "strict-transport-security",
+    "public-key-pins", "public-key-pins-report-only", "alt-svc", "clear-site-data"
+];
+
+/// tells whether `rec` is a `response` record holding an http response.
+pub fn is_http_response(rec: &WarcRecord) -> bool {
+    rec.get_type().is_ok_and(|t| t == "response")
+        && rec.get_header("Content-Type").is_some_and(|c| c.starts_with("application/http"))
+}
+
+/// http response as archived in `response` records.
+pub struct HttpResponse {
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    /// entity body, already de-chunked
+    pub body: Vec<u8>
+}
+
+impl HttpResponse {
+    pub fn parse(message: &[u8]) -> anyhow::Result<Self> {
+        let (mut response, body_start) = Self::parse_head(message)?;
+
+        let body = &message[body_start..];
+        response.body = if response.get_header("Transfer-Encoding")
+            .is_some_and(|te| te.to_ascii_lowercase().contains("chunked")) {
+            decode_chunked(body)
+        } else {
+            body.to_vec()
+        };
+
+        Ok(response)
+    }
+
+    /// parses the status line and headers only, also returns where the body starts.
+    pub fn parse_head(message: &[u8]) -> anyhow::Result<(Self, usize)> {
+        let (head_len, body_start) = match message.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(p) => (p, p + 4),
+            None => match message.windows(2).position(|w| w == b"\n\n") {
+                Some(p) => (p, p + 2),
+                None => (message.len(), message.len())
+            }
+        };
+
+        let head = String::from_utf8_lossy(&message[..head_len]);
+        let mut lines = head.lines();
+
+        let status_line = lines.next().unwrap_or_default();
+        if !status_line.starts_with("HTTP/") {
+            bail!("invalid status line");
+        }
+        let mut parts = status_line.splitn(3, ' ');
+        let status = parts.nth(1)
+            .filter(|s| s.len() == 3)
+            .and_then(|s| s.parse::<u16>().ok())
+            .context("invalid status code")?;
+        let reason = parts.next().unwrap_or_default().to_string();
+
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in lines {
+            if line.starts_with(' ') || line.starts_with('\t') {
+                if let Some(last) = headers.last_mut() {
+                    last.1.push(' ');
+                    last.1.push_str(line.trim());
+                }
+                continue;
+            }
+
+            if let Some((k, v)) = line.split_once(':') {
+                headers.push((k.trim().to_string(), v.trim().to_string()));
+            }
+        }
+
+        Ok((HttpResponse{status, reason, headers, body: Vec::new()}, body_start))
+    }
+
+    pub fn get_header(&self, k: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(hk, _)| hk.eq_ignore_ascii_case(k))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// headers that can be replayed as-is.
+    pub fn safe_headers(&self) -> impl Iterator<Item = &(String, String)> {
+        self.headers.iter()
+            .filter(|(k, _)| !UNSAFE_HEADERS.contains(&k.to_ascii_lowercase().as_str()))
+    }
+
+    /// decodes the body according to `Content-Encoding` and drops the header.
+    pub async fn decode_content_encoding(&mut self) -> anyhow::Result<()> {
+        let Some(encodings) = self.get_header("Content-Encoding").map(|e| e.to_ascii_lowercase()) else {
+            return Ok(());
+        };
+
+        /* last applied encoding is listed last */
+        for encoding in encodings.split(',').map(|e| e.trim()).rev() {
+            let mut decoded = Vec::new();
+            match encoding {
+                "" | "identity" => continue,
+                "gzip" | "x-gzip" => { GzipDecoder::new(&self.body[..]).read_to_end(&mut decoded).await?; },
+                "br" => { BrotliDecoder::new(&self.body[..]).read_to_end(&mut decoded).await?; },
+                "deflate" => {
+                    /* servers send both zlib-wrapped and raw deflate */
+                    if ZlibDecoder::new(&self.body[..]).read_to_end(&mut decoded).await.is_err() {
+                        decoded.clear();
+                        DeflateDecoder::new(&self.body[..]).read_to_end(&mut decoded).await?;
+                    }
+                },
+                _ => bail!("unsupported content encoding '{}'", encoding)
+            }
+            self.body = decoded;
+        }
+
+        self.headers.retain(|(k, _)| !k.eq_ignore_ascii_case("Content-Encoding"));
+        Ok(())
+    }
+}
+
+/// decodes a chunked body, a truncated body (common in archives) is returned as far as it goes.
+fn decode_chunked(mut body: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::new();
+
+    while let Some(line_end) = body.iter().position(|c| *c == b'\n') {
+        let size_line = String::from_utf8_lossy(&body[..line_end]);
+        let size_str = size_line.split(';').next().unwrap_or_default().trim();
+        let Ok(size) = usize::from_str_radix(size_str, 16) else {
+            break;
+        };
+        body = &body[line_end+1..];
+
+        if size == 0 {
+            break;
+        }
+
+        let size = size.min(body.len());
+        decoded.extend_from_slice(&body[..size]);
+        body = &body[size..];
+        body = body.strip_prefix(b"\r\n").or(body.strip_prefix(b"\n")).unwrap_or(body);
+    }
+
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use async_compression::tokio::bufread::{DeflateEncoder, GzipEncoder, ZlibEncoder};
+
+    use super::*;
+
+    fn response(headers: &str, body: &[u8]) -> HttpResponse {
+        let mut message = format!("HTTP/1.1 200 OK\r\n{}\r\n", headers).into_bytes();
+        message.extend_from_slice(body);
+        HttpResponse::parse(&message).unwrap()
+    }
+
+    #[test]
+    fn parses_head() {
+        let res = HttpResponse::parse(b"HTTP/1.0 404 Not Found\nX-Folded: a\n  b\nContent-Type: text/html\n\nbody").unwrap();
+        assert_eq!((res.status, res.reason.as_str()), (404, "Not Found"));
+        assert_eq!(res.get_header("x-folded"), Some("a b"));
+        assert_eq!(res.body, b"body");
+
+        assert!(HttpResponse::parse(b"GET / HTTP/1.1\r\n\r\n").is_err());
+        assert!(HttpResponse::parse(b"HTTP/1.1 20 OK\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn decodes_chunked_bodies() {
+        let res = response("Transfer-Encoding: chunked\r\n", b"5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n");
+        assert_eq!(res.body, b"hello, world");
+
+        /* truncated bodies are kept as far as they go */
+        let res = response("Transfer-Encoding: chunked\r\n", b"5\r\nhello\r\n10\r\nwor");
+        assert_eq!(res.body, b"hellowor");
+    }
+
+    #[tokio::test]
+    async fn decodes_content_encodings() {
+        let mut gzip = Vec::new();
+        GzipEncoder::new(&b"hello"[..]).read_to_end(&mut gzip).await.unwrap();
+        let mut res = response("Content-Encoding: gzip\r\n", &gzip);
+        res.decode_content_encoding().await.unwrap();
+        assert_eq!(res.body, b"hello");
+        assert!(res.get_header("Content-Encoding").is_none());
+
+        /* zlib-wrapped and raw deflate, the last listed encoding is undone first */
+        for deflated in [
+            { let mut d = Vec::new(); ZlibEncoder::new(&gzip[..]).read_to_end(&mut d).await.unwrap(); d },
+            { let mut d = Vec::new(); DeflateEncoder::new(&gzip[..]).read_to_end(&mut d).await.unwrap(); d }
+        ] {
+            let mut res = response("Content-Encoding: gzip, deflate\r\n", &deflated);
+            res.decode_content_encoding().await.unwrap();
+            assert_eq!(res.body, b"hello");
+        }
+
+        let mut res = response("Content-Encoding: compress\r\n", b"hello");
+        assert!(res.decode_content_encoding().await.is_err());
+    }
+
+    #[test]
+    fn drops_unsafe_headers() {
+        let res = response("Set-Cookie: a=b\r\nContent-Type: text/plain\r\nConnection: close\r\n", b"");
+        let names: Vec<&str> = res.safe_headers().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(names, ["Content-Type"]);
+    }
+}
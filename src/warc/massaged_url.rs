@@ -71,7 +71,9 @@ pub fn massage_url(url: &str) -> Result<String> {
     Ok(ret)
 }
 
+#[derive(Default)]
 pub enum Match {
+    #[default]
     None,
     ExactMatch(String),
     PartialMatch(String)
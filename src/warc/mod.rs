@@ -25,30 +25,35 @@ use chrono::Utc;
 use log::warn;
 use uuid::Uuid;
 
-use crate::utils::open_compressed;
+use crate::utils::{counting::CountingReader, open_compressed};
 
+pub mod arc;
 pub mod cdx;
+pub mod cdxj;
+pub mod http;
 pub mod massaged_url;
+pub mod payload;
+pub mod revisit;
+pub mod rewrite;
+pub mod scanner;
+pub mod wacz;
 
 #[derive(Debug)]
 pub struct WarcRecord {
+    version: String,
     headers: HashMap<String, Vec<String>>,
     body: Vec<u8>
 }
 
 
 fn must_overwrite_header(k: &str) -> bool {
-    match k {
-        "WARC-Type" => true,
-        "WARC-Record-ID" => true,
-        "WARC-Date" => true,
-        _ => false
-    }
+    ["WARC-Type", "WARC-Record-ID", "WARC-Date"].iter().any(|h| h.eq_ignore_ascii_case(k))
 }
 
 impl WarcRecord {
     pub fn new(typ: String) -> Self {
         let mut warc = WarcRecord{
+            version: "WARC/1.1".to_string(),
             headers: HashMap::new(),
             body: Vec::new()
         };
@@ -60,21 +65,35 @@ impl WarcRecord {
         warc
     }
 
+    pub fn get_version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn set_version(&mut self, version: String) {
+        self.version = version;
+    }
+
     // TODO: remove this function and make an iterator (so we don't copy)
     pub fn get_headers(&self) -> HashMap<String, Vec<String>> {
         self.headers.clone()
     }
 
+    /// header names are case-insensitive, the spelling already stored is kept.
+    fn header_key(&self, k: &str) -> Option<String> {
+        self.headers.keys().find(|hk| hk.eq_ignore_ascii_case(k)).cloned()
+    }
+
     pub fn set_header(&mut self, k: String, v: String) {
-        if k != "Content-Length" {
-            self.headers.insert(k.to_string(),vec![v] );
+        if !k.eq_ignore_ascii_case("Content-Length") {
+            let k = self.header_key(&k).unwrap_or(k);
+            self.headers.insert(k, vec![v]);
         }
     }
 
     pub fn add_header(&mut self, k: &str, v: String) {
         if must_overwrite_header(k) {
             self.set_header(k.to_string(), v);
-        } else if let Some(vv) = self.headers.get_mut(k) {
+        } else if let Some(vv) = self.header_key(k).and_then(|k| self.headers.get_mut(&k)) {
             vv.push(v);
         } else {
             self.set_header(k.to_string(), v);
@@ -111,11 +130,15 @@ impl WarcRecord {
     }
 
     pub fn get_header(&self, k: &str) -> Option<String> {
-        if let Some(h) = self.headers.get(k) {
-            h.get(0).cloned()
-        } else {
-            None
-        }
+        let h = match self.headers.get(k) {
+            Some(h) => h,
+            None => self.headers.iter().find(|(hk, _)| hk.eq_ignore_ascii_case(k))?.1
+        };
+        h.first().cloned()
+    }
+
+    pub fn get_body(&self) -> &[u8] {
+        &self.body
     }
 
     pub fn set_body(&mut self, body: Vec<u8>) {
@@ -123,7 +146,7 @@ impl WarcRecord {
     }
 
     pub fn write_headers<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
-        writer.write_all("WARC/1.1\r\n".as_bytes())?;
+        writer.write_fmt(format_args!("{}\r\n", self.version))?;
         for (k, v) in self.headers.iter() {
             for vv in v.iter() {
                 writer.write_fmt(format_args!("{}: {}\r\n", k, vv))?;
@@ -151,103 +174,187 @@ impl WarcRecord {
 }
 
 pub struct WarcReader {
-    br: BufReader<Box<dyn AsyncRead + Unpin + Send>>
+    br: CountingReader<BufReader<Box<dyn AsyncRead + Unpin + Send>>>
 } 
 
 impl WarcReader {
     pub fn from_fp(fp: fs::File) -> WarcReader {
-        WarcReader{
-            br: BufReader::new(Box::new(fp))
-        }
+        Self::from_bufreader(BufReader::new(Box::new(fp)))
     }
 
     pub fn from_bufreader(br: BufReader<Box<dyn AsyncRead + Unpin + Send>>) -> WarcReader{
-        WarcReader{br}
+        WarcReader{br: CountingReader::new(br)}
     }
 
     pub async fn from_file(path: &str) -> anyhow::Result<WarcReader> {
-        Ok(WarcReader{
-            br: open_compressed(path).await?
-        })
+        Ok(Self::from_bufreader(open_compressed(path).await?))
     }
-    
+
+    /// offset in the (decompressed) stream.
+    pub fn position(&self) -> u64 {
+        self.br.position()
+    }
+
     pub async fn async_next(&mut self) -> Option<WarcRecord> {
+        let offset = self.br.position();
         let ret = read_record(&mut self.br).await;
         if let Ok(x) = ret {
             x
         } else {
-            warn!("error while reading warc: {}", ret.unwrap_err());
+            warn!("error while reading warc record at offset {}: {}", offset, ret.unwrap_err());
             None
         }
     }
 }
 
-#[derive(PartialEq, Eq)]
-enum ReadRecordState {
-    WaitingWarcHeader,
-    WaitingEndOfHeaders,
-    _WaitingContent // this one is not used but still
+/// versions accepted by `read_record`, serialised back as read.
+pub const WARC_VERSIONS: [&str; 2] = ["WARC/1.0", "WARC/1.1"];
+
+/// reads a line and strips its terminator (`\r\n` or `\n`).
+async fn read_header_line<Bf: AsyncBufReadExt + Unpin>(br: &mut Bf, pos: &mut u64) -> anyhow::Result<Option<String>> {
+    let mut line: Vec<u8> = Vec::new();
+    let num = br.read_until(b'\n', &mut line).await?;
+    if num == 0 {
+        return Ok(None);
+    }
+    *pos += num as u64;
+
+    if line.ends_with(b"\n") {
+        line.pop();
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+    }
+
+    match String::from_utf8(line) {
+        Ok(l) => Ok(Some(l)),
+        Err(_) => bail!("invalid utf-8 in header line (record byte {})", pos)
+    }
 }
 
+/// reads the next record, errors report byte offsets relative to the record start.
 pub async fn read_record<Bf: AsyncBufReadExt + Unpin>(mut br: Bf) -> anyhow::Result<Option<WarcRecord>>{
     let mut contentlen: Option<usize> = None;
     let mut ret = WarcRecord::new("".to_string());
-    let mut state = ReadRecordState::WaitingWarcHeader;
-
-    loop {
-        let mut line_buffer = String::new();
-        let num = br.read_line(&mut line_buffer).await?;
-
-        if num == 0 {
-            if state == ReadRecordState::WaitingWarcHeader {
-                return Ok(None);
-            } else {
-                bail!("unexpected end of file");
-            }
+    let mut pos: u64 = 0;
+
+    /* some writers put extra newlines between records */
+    let (version_start, version) = loop {
+        let line_start = pos;
+        match read_header_line(&mut br, &mut pos).await? {
+            None => return Ok(None),
+            Some(line) if line.is_empty() => continue,
+            Some(line) => break (line_start, line)
         }
+    };
 
-        if state == ReadRecordState::WaitingWarcHeader {
-            if line_buffer != "WARC/1.1\r\n" {
-                bail!("expected 'WARC/1.1' but found '{}'", line_buffer.trim());
-            } else {
-                state = ReadRecordState::WaitingEndOfHeaders;
-                continue;
-            }
-        }
+    if !WARC_VERSIONS.contains(&version.as_str()) {
+        bail!("expected 'WARC/1.0' or 'WARC/1.1' but found '{}' (record byte {})", version.trim(), version_start);
+    }
+    ret.set_version(version);
 
-        if line_buffer == "\r\n" {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    loop {
+        let line_start = pos;
+        let Some(line) = read_header_line(&mut br, &mut pos).await? else {
+            bail!("unexpected end of file in headers (record byte {})", pos);
+        };
+
+        if line.is_empty() {
             break;
         }
 
-        if state != ReadRecordState::WaitingWarcHeader {
-            if let Some(pos) = line_buffer.find(": ") {
-                let key = &line_buffer[..pos];
-                let value = &line_buffer[pos+2..line_buffer.len()-2];
-
-                if key == "Content-Length" {
-                    contentlen = Some(value.parse::<usize>()?);
-                } else if key == "WARC-Type" {
-                    ret.set_header(key.to_string(), value[..].to_string());  // cannot have 2 type
-                } else {
-                    ret.add_header(key, value[..].to_string());
-                }
-            } else {
-                bail!("invalid header: {}", line_buffer.trim());
+        if line.starts_with([' ', '\t']) {
+            /* folded line, continues the previous header */
+            let Some((_, value)) = headers.last_mut() else {
+                bail!("continuation line without header (record byte {})", line_start);
+            };
+            if !value.is_empty() {
+                value.push(' ');
             }
+            value.push_str(line.trim());
+        } else if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        } else {
+            bail!("invalid header '{}' (record byte {})", line.trim(), line_start);
+        }
+    }
+
+    for (key, value) in headers {
+        if key.eq_ignore_ascii_case("Content-Length") {
+            contentlen = match value.parse::<usize>() {
+                Ok(len) => Some(len),
+                Err(_) => bail!("invalid Content-Length '{}'", value)
+            };
+        } else {
+            ret.add_header(&key, value); // WARC-Type, WARC-Record-ID and WARC-Date are overwritten
         }
     }
 
     if let Some(len) = contentlen {
         let mut content: Vec<u8> = Vec::new();
         content.resize(len, 0);
-        br.read_exact(&mut content[..]).await?;
+        if let Err(x) = br.read_exact(&mut content[..]).await {
+            bail!("unable to read {} bytes of content at record byte {}: {}", len, pos, x);
+        }
+        pos += len as u64;
         ret.set_body(content);
-        let mut newlines: [u8; 4] = [0,0,0,0];
-        br.read_exact(&mut newlines).await?;
-        if newlines != "\r\n\r\n".as_bytes() {
-            bail!("invalid body footer in warc record");
+
+        for _ in 0..2 {
+            match read_header_line(&mut br, &mut pos).await? {
+                Some(line) if line.is_empty() => {},
+                _ => bail!("invalid body footer in warc record (record byte {})", pos)
+            }
         }
     }
 
     Ok(Some(ret))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn parse(data: &[u8]) -> WarcRecord {
+        read_record(data).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_warc_1_0() {
+        let rec = parse(b"WARC/1.0\r\nWARC-Type: resource\r\nWARC-Record-ID: <urn:uuid:1>\r\nContent-Length: 5\r\n\r\nhello\r\n\r\n").await;
+        assert_eq!(rec.get_version(), "WARC/1.0");
+        assert_eq!(rec.get_type().unwrap(), "resource");
+        assert_eq!(rec.get_record_id().unwrap(), "urn:uuid:1");
+        assert_eq!(rec.get_body(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn reads_bare_lf() {
+        let data = b"WARC/1.1\nWARC-Type: resource\nContent-Length: 5\n\nhello\n\nWARC/1.1\nWARC-Type: metadata\nContent-Length: 0\n\n\n\n";
+        let mut br = &data[..];
+        let first = read_record(&mut br).await.unwrap().unwrap();
+        assert_eq!(first.get_body(), b"hello");
+        let second = read_record(&mut br).await.unwrap().unwrap();
+        assert_eq!(second.get_type().unwrap(), "metadata");
+        assert!(read_record(&mut br).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn reads_folded_headers() {
+        let rec = parse(b"WARC/1.1\r\nWARC-Type: resource\r\nWARC-Target-URI: http://example.com/\r\n  folded\r\n\tvalue\r\nContent-Length: 0\r\n\r\n\r\n\r\n").await;
+        assert_eq!(rec.get_target_uri().unwrap(), "http://example.com/ folded value");
+    }
+
+    #[tokio::test]
+    async fn matches_headers_case_insensitively() {
+        let rec = parse(b"WARC/1.1\r\nwarc-type: resource\r\nWARC-TYPE: metadata\r\ncontent-length: 2\r\n\r\nok\r\n\r\n").await;
+        assert_eq!(rec.get_type().unwrap(), "metadata");
+        assert_eq!(rec.get_headers().keys().filter(|k| k.eq_ignore_ascii_case("WARC-Type")).count(), 1);
+        assert_eq!(rec.get_body(), b"ok");
+    }
+
+    #[tokio::test]
+    async fn rejects_continuation_without_header() {
+        assert!(read_record(&b"WARC/1.1\r\n folded\r\n\r\n"[..]).await.is_err());
+    }
+}
@@ -0,0 +1,86 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use data_encoding::BASE32;
+use sha1::{Digest, Sha1};
+
+use super::{http::HttpResponse, WarcRecord};
+
+/// what the index knows about a record's payload.
+#[derive(Default)]
+pub struct PayloadInfo {
+    pub status: Option<u16>,
+    pub mime: Option<String>,
+    pub length: Option<u64>,
+    /// `WARC-Payload-Digest`, computed when the record has none
+    pub digest: Option<String>
+}
+
+impl PayloadInfo {
+    pub fn from_warc(warc: &WarcRecord, record_type: &str) -> Self {
+        let mut info = PayloadInfo{
+            mime: warc.get_header("Content-Type").map(|c| mime_essence(&c)),
+            ..Default::default()
+        };
+        let mut payload = Some(warc.get_body());
+
+        /* http messages: the payload is the entity body */
+        if info.mime.as_deref() == Some("application/http") {
+            match HttpResponse::parse_head(warc.get_body()) {
+                Ok((http, body_start)) => {
+                    info.status = Some(http.status);
+                    info.mime = http.get_header("Content-Type").map(mime_essence);
+                    payload = Some(&warc.get_body()[body_start..]);
+                },
+                Err(_) => payload = None
+            }
+        }
+
+        match record_type {
+            "response" | "resource" | "conversion" => {},
+            _ => payload = None
+        }
+
+        if let Some(payload) = payload {
+            info.length = Some(payload.len() as u64);
+            info.digest = Some(warc.get_header("WARC-Payload-Digest")
+                .unwrap_or_else(|| payload_digest(payload)));
+        } else {
+            info.digest = warc.get_header("WARC-Payload-Digest");
+        }
+
+        /* both end up in space-separated index lines */
+        info.mime = info.mime.filter(|m| is_index_safe(m));
+        info.digest = info.digest.map(|d| d.trim().to_string()).filter(|d| is_index_safe(d));
+        info
+    }
+}
+
+/// `sha1:` followed by the base32 sha1 of `payload`, as written by most crawlers.
+pub fn payload_digest(payload: &[u8]) -> String {
+    format!("sha1:{}", BASE32.encode(&Sha1::digest(payload)))
+}
+
+/// `text/html; charset=utf-8` -> `text/html`
+pub fn mime_essence(content_type: &str) -> String {
+    content_type.split(';').next().unwrap_or("").trim().to_lowercase()
+}
+
+fn is_index_safe(value: &str) -> bool {
+    !value.is_empty() && value != "-" && !value.contains(char::is_whitespace)
+}
@@ -0,0 +1,95 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use super::{http::HttpResponse, WarcRecord};
+
+/// headers describing the block of the record a revisit replaces (or of the revisit itself).
+const BLOCK_HEADERS: [&str; 9] = [
+    "WARC-Type", "WARC-Profile", "WARC-Block-Digest", "WARC-Truncated",
+    "WARC-Segment-Number", "WARC-Identified-Payload-Type",
+    "WARC-Refers-To", "WARC-Refers-To-Target-URI", "WARC-Refers-To-Date"
+];
+
+/// `WARC-Profile` of revisits whose payload is identical to the one of the record they refer to.
+pub fn identical_payload_profile(version: &str) -> String {
+    format!("http://netpreserve.org/warc/{}/revisit/identical-payload-digest",
+        if version == "WARC/1.0" {"1.0"} else {"1.1"})
+}
+
+/// tells whether `rec` is a revisit whose payload must be taken from the record it refers to.
+pub fn is_identical_payload_revisit(rec: &WarcRecord) -> bool {
+    rec.get_type().is_ok_and(|t| t == "revisit")
+        && rec.get_header("WARC-Profile").is_some_and(|p| p.ends_with("/revisit/identical-payload-digest"))
+}
+
+/// a revisit standing in for the http response `record`, whose payload (`digest`)
+/// is identical to the one of `original`: the http head is kept, the payload is dropped.
+pub fn make_revisit(record: &WarcRecord, original: &WarcRecord, digest: &str) -> anyhow::Result<WarcRecord> {
+    let (_, body_start) = HttpResponse::parse_head(record.get_body())?;
+
+    let mut revisit = copy_headers(record, "revisit");
+    revisit.set_header("WARC-Profile".to_string(), identical_payload_profile(record.get_version()));
+    revisit.set_header("WARC-Refers-To".to_string(), original.get_header_or_err("WARC-Record-ID")?);
+    if let Some(uri) = original.get_target_uri() {
+        revisit.set_header("WARC-Refers-To-Target-URI".to_string(), uri);
+    }
+    revisit.set_header("WARC-Refers-To-Date".to_string(), original.get_header_or_err("WARC-Date")?);
+    revisit.set_header("WARC-Payload-Digest".to_string(), digest.to_string());
+    revisit.set_body(record.get_body()[..body_start].to_vec());
+
+    Ok(revisit)
+}
+
+/// rebuilds the response `revisit` stands for, using the payload of `original`
+/// (and its http head when the revisit holds none).
+pub fn resolve_revisit(revisit: &WarcRecord, original: &WarcRecord) -> anyhow::Result<WarcRecord> {
+    let (_, payload_start) = HttpResponse::parse_head(original.get_body())?;
+
+    let head = match HttpResponse::parse_head(revisit.get_body()) {
+        Ok((_, head_len)) => &revisit.get_body()[..head_len],
+        Err(_) => &original.get_body()[..payload_start]
+    };
+
+    let mut response = copy_headers(revisit, "response");
+    if let Some(content_type) = original.get_header("Content-Type") {
+        response.set_header("Content-Type".to_string(), content_type);
+    }
+    if let Some(block_digest) = original.get_header("WARC-Block-Digest") && head == &original.get_body()[..payload_start] {
+        response.set_header("WARC-Block-Digest".to_string(), block_digest);
+    }
+    response.set_body([head, &original.get_body()[payload_start..]].concat());
+
+    Ok(response)
+}
+
+/// a record of type `typ` with the headers of `rec`, except those describing its block.
+fn copy_headers(rec: &WarcRecord, typ: &str) -> WarcRecord {
+    let mut copy = WarcRecord::new(typ.to_string());
+    copy.set_version(rec.get_version().to_string());
+
+    for (k, values) in rec.get_headers() {
+        if BLOCK_HEADERS.iter().any(|h| h.eq_ignore_ascii_case(&k)) {
+            continue;
+        }
+        for v in values {
+            copy.add_header(&k, v);
+        }
+    }
+
+    copy
+}
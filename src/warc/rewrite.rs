@@ -0,0 +1,196 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use std::{borrow::Cow, sync::LazyLock};
+
+use regex::bytes::{Captures, Regex};
+use url::Url;
+
+static HTML_TAG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+    r"(?is)<!--.*?-->|<script\b[^>]*>.*?</script\s*>|<style\b[^>]*>.*?</style\s*>|<[a-z][^>]*>").unwrap());
+static HTML_ATTR_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+    r#"(?i)(\s(href|src|srcset|style|action|poster|data|content)\s*=\s*)(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap());
+static HTML_META_REFRESH_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+    r#"(?i)^<meta\b[^>]*\shttp-equiv\s*=\s*["']?refresh\b"#).unwrap());
+static HTML_BASE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+    r#"(?i)<base\b[^>]*\shref\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap());
+static CSS_URL_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+    r#"(?i)url\(\s*(?:"([^"]*)"|'([^']*)'|([^)"'\s]*))\s*\)|@import\s+(?:"([^"]*)"|'([^']*)')"#).unwrap());
+
+/// kind of documents whose links can be rewritten.
+pub enum DocumentKind {
+    Html,
+    Css
+}
+
+impl DocumentKind {
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        match mime.split(';').next().unwrap_or_default().trim().to_lowercase().as_str() {
+            "text/html" | "application/xhtml+xml" => Some(DocumentKind::Html),
+            "text/css" => Some(DocumentKind::Css),
+            _ => None
+        }
+    }
+}
+
+/// rewrites the links of an archived document so they point to `prefix` + absolute url
+/// (e.g. `/url/p/20250101000000/`) instead of the live web.
+pub struct LinkRewriter {
+    base: Url,
+    prefix: String
+}
+
+impl LinkRewriter {
+    pub fn new(document_url: &str, prefix: String) -> anyhow::Result<Self> {
+        Ok(LinkRewriter{base: Url::parse(document_url)?, prefix})
+    }
+
+    pub fn rewrite(&mut self, kind: &DocumentKind, document: &[u8]) -> Vec<u8> {
+        match kind {
+            DocumentKind::Html => self.rewrite_html(document),
+            DocumentKind::Css => self.rewrite_css(document)
+        }
+    }
+
+    /// returns `None` for links that must be left as-is (fragments, `data:`, `javascript:`...).
+    pub fn rewrite_url(&self, link: &str) -> Option<String> {
+        let link = link.trim();
+        if link.is_empty() || link.starts_with('#') || link.starts_with(&self.prefix) {
+            return None;
+        }
+
+        let url = self.base.join(link).ok()?;
+        match url.scheme() {
+            "http" | "https" => Some(format!("{}{}", self.prefix, url)),
+            _ => None
+        }
+    }
+
+    pub fn rewrite_html(&mut self, html: &[u8]) -> Vec<u8> {
+        /* every relative link depends on <base> */
+        if let Some(base) = HTML_BASE_RE.captures(html)
+            .and_then(|c| c.get(1).or(c.get(2)).or(c.get(3)))
+            .and_then(|m| self.base.join(&html_unescape(m.as_bytes())).ok()) {
+            self.base = base;
+        }
+
+        HTML_TAG_RE.replace_all(html, |c: &Captures| {
+            let tag = &c[0];
+            if tag.starts_with(b"<!--") {
+                return tag.to_vec();
+            }
+
+            /* only the opening tag of scripts, style contents are css */
+            let open_end = tag.iter().position(|c| *c == b'>').map(|p| p + 1).unwrap_or(tag.len());
+            let mut ret = self.rewrite_html_attributes(&tag[..open_end]);
+            if tag[..open_end].to_ascii_lowercase().starts_with(b"<style") {
+                let close_start = tag.len() - tag.iter().rev().position(|c| *c == b'<').map(|p| p + 1).unwrap_or(0);
+                ret.extend(self.rewrite_css(&tag[open_end..close_start]));
+                ret.extend_from_slice(&tag[close_start..]);
+            } else {
+                ret.extend_from_slice(&tag[open_end..]);
+            }
+            ret
+        }).into_owned()
+    }
+
+    fn rewrite_html_attributes(&self, tag: &[u8]) -> Vec<u8> {
+        /* `content` only holds a link in `<meta http-equiv="refresh">` */
+        let is_refresh = HTML_META_REFRESH_RE.is_match(tag);
+
+        HTML_ATTR_RE.replace_all(tag, |c: &Captures| {
+            let value = html_unescape(c.get(3).or(c.get(4)).or(c.get(5)).map(|m| m.as_bytes()).unwrap_or_default());
+            let rewritten = match c[2].to_ascii_lowercase().as_slice() {
+                b"srcset" => Some(self.rewrite_srcset(&value)),
+                b"style" => Some(String::from_utf8_lossy(&self.rewrite_css(value.as_bytes())).into_owned()),
+                b"content" if is_refresh => self.rewrite_refresh(&value),
+                b"content" => None,
+                _ => self.rewrite_url(&value)
+            };
+
+            match rewritten {
+                Some(v) => [&c[1], b"\"", html_escape(&v).as_bytes(), b"\""].concat(),
+                None => c[0].to_vec()
+            }
+        }).into_owned()
+    }
+
+    /// `5; url=/next` -> `5; url=[prefix]/next`
+    fn rewrite_refresh(&self, content: &str) -> Option<String> {
+        let (delay, target) = content.split_once([';', ','])?;
+        let target = target.trim_start();
+        let target = match target.get(..3) {
+            Some(k) if k.eq_ignore_ascii_case("url") => target[3..].trim_start().strip_prefix('=')?,
+            _ => target
+        };
+        let target = target.trim().trim_matches(|c| c == '"' || c == '\'');
+
+        Some(format!("{}; url={}", delay.trim(), self.rewrite_url(target)?))
+    }
+
+    fn rewrite_srcset(&self, srcset: &str) -> String {
+        srcset.split(',')
+            .map(|candidate| {
+                let candidate = candidate.trim();
+                let (url, descriptor) = candidate.split_once(char::is_whitespace).unwrap_or((candidate, ""));
+                match self.rewrite_url(url) {
+                    Some(u) if descriptor.is_empty() => u,
+                    Some(u) => format!("{} {}", u, descriptor.trim()),
+                    None => candidate.to_string()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    pub fn rewrite_css(&self, css: &[u8]) -> Vec<u8> {
+        CSS_URL_RE.replace_all(css, |c: &Captures| {
+            let is_import = c.get(4).is_some() || c.get(5).is_some();
+            let link = (1..=5).find_map(|i| c.get(i)).map(|m| m.as_bytes()).unwrap_or_default();
+
+            match self.rewrite_url(&String::from_utf8_lossy(link)) {
+                Some(u) if is_import => format!("@import \"{}\"", u.replace('"', "%22")).into_bytes(),
+                Some(u) => format!("url(\"{}\")", u.replace('"', "%22")).into_bytes(),
+                None => c[0].to_vec()
+            }
+        }).into_owned()
+    }
+}
+
+fn html_unescape(value: &[u8]) -> String {
+    let value = String::from_utf8_lossy(value);
+    if !value.contains('&') {
+        return value.into_owned();
+    }
+
+    value.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&#38;", "&")
+        .replace("&amp;", "&")
+}
+
+fn html_escape(value: &str) -> Cow<'_, str> {
+    if !value.contains(['&', '"']) {
+        return Cow::Borrowed(value);
+    }
+
+    Cow::Owned(value.replace('&', "&amp;").replace('"', "&quot;"))
+}
@@ -0,0 +1,128 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_compression::tokio::bufread::{GzipDecoder, ZstdDecoder};
+use tokio::{fs, io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader}};
+
+use crate::utils::counting::CountingReader;
+
+use super::{read_record, WarcRecord};
+
+/// how each record of a file is compressed.
+#[derive(Clone)]
+pub enum RecordCompression {
+    None,
+    /// one gzip member per record (`.warc.gz`)
+    Gzip,
+    /// one zstd frame per record, using the given dictionary
+    Zstd(Arc<Vec<u8>>)
+}
+
+pub struct ScannedRecord {
+    pub offset: u64,
+    /// stored (compressed) size
+    pub size: u64,
+    pub record: WarcRecord,
+    /// stored bytes, when the scanner keeps them
+    pub raw: Option<Vec<u8>>
+}
+
+/// walks a record file without relying on its index.
+pub struct WarcFileScanner {
+    br: CountingReader<BufReader<fs::File>>,
+    raw_fp: Option<BufReader<fs::File>>,
+    compression: RecordCompression
+}
+
+impl WarcFileScanner {
+    pub async fn open(path: &str, compression: RecordCompression) -> anyhow::Result<Self> {
+        Ok(WarcFileScanner{
+            br: CountingReader::new(BufReader::new(fs::File::open(path).await?)),
+            raw_fp: None,
+            compression
+        })
+    }
+
+    /// also returns the stored bytes of each record.
+    pub async fn keep_raw(mut self, path: &str) -> anyhow::Result<Self> {
+        self.raw_fp = Some(BufReader::new(fs::File::open(path).await?));
+        Ok(self)
+    }
+
+    /// checks the first record of the file can be read with `compression`,
+    /// e.g. to tell per-record `.warc.gz` apart from fully gzipped files.
+    pub async fn probe(path: &str, compression: RecordCompression) -> bool {
+        match Self::open(path, compression).await {
+            Ok(mut scanner) => matches!(scanner.async_next().await, Ok(Some(_))),
+            Err(_) => false
+        }
+    }
+
+    pub async fn async_next(&mut self) -> anyhow::Result<Option<ScannedRecord>> {
+        let offset = self.br.position();
+        self.read_next(offset).await.with_context(|| format!("record at offset {}", offset))
+    }
+
+    async fn read_next(&mut self, offset: u64) -> anyhow::Result<Option<ScannedRecord>> {
+        if self.br.fill_buf().await?.is_empty() {
+            return Ok(None);
+        }
+
+        let record = match &self.compression {
+            RecordCompression::None => read_record(&mut self.br).await?,
+            RecordCompression::Gzip => read_whole_member(
+                BufReader::new(GzipDecoder::new(&mut self.br))).await?,
+            RecordCompression::Zstd(dict) => read_whole_member(
+                BufReader::new(ZstdDecoder::with_dict(&mut self.br, &dict[..])?)).await?
+        };
+
+        let Some(record) = record else {
+            anyhow::bail!("incomplete record");
+        };
+
+        let size = self.br.position() - offset;
+        let raw = match &mut self.raw_fp {
+            Some(fp) => {
+                let mut raw = vec![0; size as usize];
+                fp.read_exact(&mut raw).await?;
+                Some(raw)
+            },
+            None => None
+        };
+
+        Ok(Some(ScannedRecord{offset, size, record, raw}))
+    }
+}
+
+/// reads a record then reaches the end of its frame (or member)
+/// so its last bytes are counted.
+async fn read_whole_member<R: AsyncBufRead + Unpin>(mut decoder: R) -> anyhow::Result<Option<WarcRecord>> {
+    let record = read_record(&mut decoder).await?;
+
+    // a single byte is enough to tell the member holds more than one record,
+    // no need to decompress the rest of the file.
+    let mut trailing = [0u8; 1];
+    if decoder.read(&mut trailing).await? > 0 {
+        anyhow::bail!("unexpected bytes after the record");
+    }
+
+    Ok(record)
+}
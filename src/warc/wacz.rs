@@ -0,0 +1,275 @@
+/**
+ *  This file is part of Masstuffy. Masstuffy is free software:
+ *  you can redistribute it and/or modify it under the terms of 
+ *  the GNU Affero General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License,
+ *  or (at your option) any later version.
+ * 
+ *  Masstuffy is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+ * 
+ *  See the GNU Affero General Public License for more details.
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with Masstuffy. If not, see <https://www.gnu.org/licenses/>. 
+ * 
+ *  Copyright (C) 2025 5IGI0 / Ethan L. C. Lorenzetti
+**/
+
+use std::{fs::File, io::{self, Read, Seek, Write}};
+
+use async_compression::tokio::bufread::GzipEncoder;
+use anyhow::{bail, Context};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::constants::MASSTUFFY_DATE_FMT;
+
+use super::{cdx::CDXRecord, cdxj::{sort_entries, CDXJEntry}, WarcRecord};
+
+const WACZ_VERSION: &str = "1.1.1";
+const WACZ_WARC_NAME: &str = "data.warc.gz";
+
+/// writes a WACZ package (as read by ReplayWeb.page) from records,
+/// records are streamed into the archive while indexes are kept in memory.
+pub struct WaczWriter<W: Write + Seek> {
+    zip: ZipWriter<W>,
+    title: String,
+    warc_hash: Sha256,
+    warc_size: u64,
+    entries: Vec<CDXJEntry>,
+    pages: Vec<String>
+}
+
+impl<W: Write + Seek> WaczWriter<W> {
+    pub fn new(writer: W, title: &str) -> anyhow::Result<Self> {
+        let mut zip = ZipWriter::new(writer);
+
+        /* already compressed, and must stay seekable for replay */
+        zip.start_file(
+            format!("archive/{}", WACZ_WARC_NAME),
+            SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Stored)
+                .large_file(true))?;
+
+        Ok(WaczWriter{
+            zip,
+            title: title.to_string(),
+            warc_hash: Sha256::new(),
+            warc_size: 0,
+            entries: Vec::new(),
+            pages: Vec::new()
+        })
+    }
+
+    /// appends a record as its own gzip member, so it can be indexed.
+    pub async fn add_record(&mut self, record: &WarcRecord) -> anyhow::Result<()> {
+        let mut member = Vec::new();
+        GzipEncoder::new(&record.serialize()[..]).read_to_end(&mut member).await?;
+
+        let offset = self.warc_size;
+        self.zip.write_all(&member)?;
+        self.warc_hash.update(&member);
+        self.warc_size += member.len() as u64;
+
+        let Some(url) = record.get_target_uri() else {
+            return Ok(());
+        };
+
+        let date = record.get_date()?;
+        let cdx = CDXRecord::from_warc(record)?;
+        let entry = CDXJEntry::new(
+            &url, &date.format(MASSTUFFY_DATE_FMT).to_string(),
+            WACZ_WARC_NAME.to_string(), offset, member.len() as u64)?
+            .with_details(&cdx.get_record_type(), cdx.get_mime(), cdx.get_status(), cdx.get_payload_digest());
+
+        if record.get_type()? == "response"
+            && entry.block.mime.as_deref() == Some("text/html")
+            && entry.block.status.as_deref().is_some_and(|s| s.starts_with('2')) {
+            self.pages.push(json!({
+                "id": record.get_record_id()?,
+                "url": url,
+                "ts": date.to_rfc3339_opts(SecondsFormat::Secs, true)
+            }).to_string());
+        }
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// writes indexes, pages and the datapackage then returns the underlying writer.
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        let mut resources = vec![json!({
+            "name": WACZ_WARC_NAME,
+            "path": format!("archive/{}", WACZ_WARC_NAME),
+            "hash": format!("sha256:{:x}", self.warc_hash.clone().finalize()),
+            "bytes": self.warc_size
+        })];
+
+        sort_entries(&mut self.entries);
+        let mut index = String::new();
+        for entry in &self.entries {
+            index.push_str(&entry.to_cdxj());
+            index.push('\n');
+        }
+        resources.push(self.add_file("indexes/index.cdxj", index.as_bytes())?);
+
+        let mut pages = json!({"format": "json-pages-1.0", "id": "pages", "title": "All Pages"}).to_string();
+        pages.push('\n');
+        for page in &self.pages {
+            pages.push_str(page);
+            pages.push('\n');
+        }
+        resources.push(self.add_file("pages/pages.jsonl", pages.as_bytes())?);
+
+        let datapackage = serde_json::to_vec_pretty(&json!({
+            "profile": "data-package",
+            "wacz_version": WACZ_VERSION,
+            "title": self.title,
+            "created": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            "software": format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            "resources": resources
+        }))?;
+        let datapackage_hash = self.add_file("datapackage.json", &datapackage)?["hash"].clone();
+
+        let digest = serde_json::to_vec_pretty(&json!({
+            "path": "datapackage.json",
+            "hash": datapackage_hash
+        }))?;
+        self.add_file("datapackage-digest.json", &digest)?;
+
+        Ok(self.zip.finish()?)
+    }
+
+    /// adds a file and returns its datapackage resource.
+    fn add_file(&mut self, path: &str, content: &[u8]) -> anyhow::Result<Value> {
+        self.zip.start_file(path, SimpleFileOptions::default())?;
+        self.zip.write_all(content)?;
+
+        Ok(json!({
+            "name": path.rsplit('/').next().unwrap_or(path),
+            "path": path,
+            "hash": format!("sha256:{:x}", Sha256::digest(content)),
+            "bytes": content.len()
+        }))
+    }
+}
+
+/// reads a WACZ package, every file listed in its datapackage is checked when opening.
+pub struct WaczReader {
+    zip: ZipArchive<File>,
+    datapackage: Value
+}
+
+impl WaczReader {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let mut zip = ZipArchive::new(File::open(path)?)?;
+
+        let mut datapackage = Vec::new();
+        zip.by_name("datapackage.json")
+            .context("datapackage.json is missing")?
+            .read_to_end(&mut datapackage)?;
+
+        if zip.index_for_name("datapackage-digest.json").is_some() {
+            let digest: Value = serde_json::from_reader(zip.by_name("datapackage-digest.json")?)?;
+            if let Some(hash) = digest["hash"].as_str() {
+                check_hash("datapackage.json", hash, &format!("{:x}", Sha256::digest(&datapackage)))?;
+            }
+        }
+
+        let mut reader = WaczReader{
+            zip,
+            datapackage: serde_json::from_slice(&datapackage).context("invalid datapackage.json")?
+        };
+        reader.verify()?;
+        Ok(reader)
+    }
+
+    fn verify(&mut self) -> anyhow::Result<()> {
+        let resources = self.datapackage["resources"].as_array().cloned().unwrap_or_default();
+        let mut listed = Vec::new();
+
+        for resource in resources {
+            let (Some(path), Some(hash)) = (resource["path"].as_str(), resource["hash"].as_str()) else {
+                bail!("datapackage resource without path or hash: {}", resource);
+            };
+
+            let mut hasher = Sha256::new();
+            let size = io::copy(
+                &mut self.zip.by_name(path).with_context(|| format!("{} is missing", path))?,
+                &mut hasher)?;
+
+            check_hash(path, hash, &format!("{:x}", hasher.finalize()))?;
+            if let Some(bytes) = resource["bytes"].as_u64() && bytes != size {
+                bail!("{}: expected {} bytes, got {}", path, bytes, size);
+            }
+            listed.push(path.to_string());
+        }
+
+        /* records that can't be checked are not imported */
+        for path in self.warc_paths() {
+            if !listed.contains(&path) {
+                bail!("{} is not listed in datapackage.json", path);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `archive/` files, in package order.
+    pub fn warc_paths(&self) -> Vec<String> {
+        self.zip.file_names()
+            .filter(|f| f.starts_with("archive/") && (f.ends_with(".warc") || f.ends_with(".warc.gz")))
+            .map(|f| f.to_string())
+            .collect()
+    }
+
+    /// copies a file of the package to `dest`.
+    pub fn extract(&mut self, path: &str, dest: &str) -> anyhow::Result<()> {
+        io::copy(&mut self.zip.by_name(path)?, &mut File::create(dest)?)?;
+        Ok(())
+    }
+
+    /// a `metadata` record for each pages list, `name` identifies the package in their target uri.
+    pub fn page_records(&mut self, name: &str) -> anyhow::Result<Vec<WarcRecord>> {
+        let date = self.datapackage["created"].as_str()
+            .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+            .map(|d| d.with_timezone(&Utc))
+            .unwrap_or(Utc::now());
+
+        let paths: Vec<String> = self.zip.file_names()
+            .filter(|f| f.starts_with("pages/") && f.ends_with(".jsonl"))
+            .map(|f| f.to_string())
+            .collect();
+
+        let mut records = Vec::new();
+        for path in paths {
+            let mut body = Vec::new();
+            self.zip.by_name(&path)?.read_to_end(&mut body)?;
+
+            let mut record = WarcRecord::new("metadata".to_string());
+            record.set_header("WARC-Target-URI".to_string(), format!("wacz:{}/{}", name, path));
+            record.set_header("WARC-Date".to_string(), date.to_rfc3339_opts(SecondsFormat::Secs, true));
+            record.set_header("Content-Type".to_string(), "application/x-ndjson".to_string());
+            record.set_body(body);
+            records.push(record);
+        }
+
+        Ok(records)
+    }
+}
+
+fn check_hash(path: &str, expected: &str, sha256: &str) -> anyhow::Result<()> {
+    let Some(expected) = expected.strip_prefix("sha256:") else {
+        bail!("{}: unsupported hash '{}'", path, expected);
+    };
+
+    if !expected.eq_ignore_ascii_case(sha256) {
+        bail!("{}: sha256 mismatch (expected {}, got {})", path, expected, sha256);
+    }
+
+    Ok(())
+}